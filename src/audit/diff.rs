@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use crate::person::model::{ItemGroupTag, ItemTag, Person};
+use crate::profile::model::Profile;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+fn diff_value<T: PartialEq + Display>(
+    diff_list: &mut Vec<String>,
+    name: &str,
+    before: T,
+    after: T,
+) {
+    if before != after {
+        diff_list.push(format!("{}: {} -> {}", name, before, after));
+    }
+}
+
+fn count_item_group(item_list: &[ItemGroupTag]) -> BTreeMap<&str, i64> {
+    let mut count_map = BTreeMap::new();
+
+    for item in item_list {
+        *count_map.entry(item.key.as_str()).or_insert(0) += item.amount as i64;
+    }
+
+    count_map
+}
+
+// 按物品 key 汇总数量后比较, 输出 "+key x1" / "-key x1"
+fn diff_item_group(
+    diff_list: &mut Vec<String>,
+    name: &str,
+    before: &[ItemGroupTag],
+    after: &[ItemGroupTag],
+) {
+    let before_map = count_item_group(before);
+    let after_map = count_item_group(after);
+
+    for (key, after_amount) in after_map.iter() {
+        let before_amount = before_map.get(key).copied().unwrap_or(0);
+
+        if *after_amount > before_amount {
+            diff_list.push(format!(
+                "{}: +{} x{}",
+                name,
+                key,
+                after_amount - before_amount
+            ));
+        }
+    }
+
+    for (key, before_amount) in before_map.iter() {
+        let after_amount = after_map.get(key).copied().unwrap_or(0);
+
+        if after_amount < *before_amount {
+            diff_list.push(format!(
+                "{}: -{} x{}",
+                name,
+                key,
+                before_amount - after_amount
+            ));
+        }
+    }
+}
+
+fn map_item_slot(item_list: &[ItemTag]) -> BTreeMap<i8, (&str, i8)> {
+    item_list
+        .iter()
+        .map(|item| (item.slot, (item.key.as_str(), item.amount)))
+        .collect()
+}
+
+pub fn diff_person(before: &Person, after: &Person) -> Vec<String> {
+    let mut diff_list = vec![];
+
+    diff_value(&mut diff_list, "name", &before.name, &after.name);
+    diff_value(
+        &mut diff_list,
+        "max_authority_reached",
+        before.max_authority_reached,
+        after.max_authority_reached,
+    );
+    diff_value(
+        &mut diff_list,
+        "authority",
+        before.authority,
+        after.authority,
+    );
+    diff_value(
+        &mut diff_list,
+        "job_points",
+        before.job_points,
+        after.job_points,
+    );
+    diff_value(&mut diff_list, "faction", &before.faction, &after.faction);
+    diff_value(&mut diff_list, "version", before.version, after.version);
+    diff_value(&mut diff_list, "alive", before.alive, after.alive);
+    diff_value(
+        &mut diff_list,
+        "soldier_group_id",
+        before.soldier_group_id,
+        after.soldier_group_id,
+    );
+    diff_value(
+        &mut diff_list,
+        "soldier_group_name",
+        &before.soldier_group_name,
+        &after.soldier_group_name,
+    );
+    diff_value(
+        &mut diff_list,
+        "squad_size_setting",
+        before.squad_size_setting,
+        after.squad_size_setting,
+    );
+    diff_value(
+        &mut diff_list,
+        "backpack_hard_capacity",
+        before.backpack_hard_capacity,
+        after.backpack_hard_capacity,
+    );
+    diff_value(
+        &mut diff_list,
+        "stash_hard_capacity",
+        before.stash_hard_capacity,
+        after.stash_hard_capacity,
+    );
+
+    // 装备栏按 slot 比较, 缺少的 slot 视为空
+    let before_slot_map = map_item_slot(&before.item_list);
+    let after_slot_map = map_item_slot(&after.item_list);
+    let slot_set: BTreeSet<i8> = before_slot_map
+        .keys()
+        .chain(after_slot_map.keys())
+        .copied()
+        .collect();
+
+    for slot in slot_set {
+        let (before_key, before_amount) = before_slot_map.get(&slot).copied().unwrap_or(("", 0));
+        let (after_key, after_amount) = after_slot_map.get(&slot).copied().unwrap_or(("", 0));
+
+        if (before_key, before_amount) != (after_key, after_amount) {
+            diff_list.push(format!(
+                "item slot {}: {} x{} -> {} x{}",
+                slot, before_key, before_amount, after_key, after_amount
+            ));
+        }
+    }
+
+    diff_item_group(
+        &mut diff_list,
+        "backpack",
+        &before.backpack_item_list,
+        &after.backpack_item_list,
+    );
+    diff_item_group(
+        &mut diff_list,
+        "stash",
+        &before.stash_item_list,
+        &after.stash_item_list,
+    );
+
+    diff_list
+}
+
+// 将 json 对象展开为 "stats.kills" -> value 形式
+fn flatten_json(prefix: &str, value: &Value, flat_map: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let next_prefix = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+
+                flatten_json(&next_prefix, value, flat_map);
+            }
+        }
+        _ => {
+            flat_map.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
+
+pub fn diff_profile(before: &Profile, after: &Profile) -> Vec<String> {
+    let mut before_map = BTreeMap::new();
+    let mut after_map = BTreeMap::new();
+
+    flatten_json(
+        "",
+        &serde_json::to_value(before).unwrap_or_default(),
+        &mut before_map,
+    );
+    flatten_json(
+        "",
+        &serde_json::to_value(after).unwrap_or_default(),
+        &mut after_map,
+    );
+
+    after_map
+        .iter()
+        .filter_map(|(key, after_value)| {
+            let before_value = before_map.get(key)?;
+
+            (before_value != after_value)
+                .then(|| format!("{}: {} -> {}", key, before_value, after_value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_person;
+    use crate::person::model::{ItemTag, Person};
+
+    #[test]
+    fn diff_item_slot() {
+        let item = |slot: i8, key: &str| ItemTag {
+            slot,
+            amount: 1,
+            key: key.to_string(),
+            ..ItemTag::default()
+        };
+
+        let before = Person {
+            item_list: vec![item(0, "m9.weapon"), item(1, "ak47.weapon")],
+            ..Person::default()
+        };
+        let after = Person {
+            item_list: vec![
+                item(1, "ak47.weapon"),
+                item(0, "m9.weapon"),
+                item(2, "grenade"),
+            ],
+            ..Person::default()
+        };
+
+        assert_eq!(
+            diff_person(&before, &after),
+            ["item slot 2:  x0 -> grenade x1"]
+        );
+    }
+}
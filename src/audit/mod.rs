@@ -0,0 +1,5 @@
+// SPDX-License-Identifier: GPL-3.0-only
+pub mod diff;
+pub mod model;
+pub mod store;
+pub mod utils;
@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use serde::{Deserialize, Serialize};
+
+// 审计日志单条记录, 以 JSON lines 形式追加写入
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditRecord {
+    // unix 时间戳(秒)
+    pub time: i64,
+    pub user: String,
+    pub user_id: u64,
+    pub profile_id: u64,
+    pub endpoint: String,
+    // 修改前后差异摘要
+    pub diff_list: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub profile_id: Option<u64>,
+    pub user: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    // 仅返回最近的 n 条
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn is_match(&self, record: &AuditRecord) -> bool {
+        self.profile_id.is_none_or(|id| record.profile_id == id)
+            && self.user.as_ref().is_none_or(|user| &record.user == user)
+            && self.start_time.is_none_or(|time| record.time >= time)
+            && self.end_time.is_none_or(|time| record.time <= time)
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{AuditQuery, AuditRecord};
+use crate::constant::AUDIT_LOG_FILE_NAME;
+use anyhow::Result;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+// 审计日志只追加写入, 写入时加锁避免多条记录交错
+#[derive(Debug)]
+pub struct AuditLog {
+    file_path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(data_path: &str) -> Self {
+        Self {
+            file_path: Path::new(data_path).join(AUDIT_LOG_FILE_NAME),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn append(&self, record_list: &[AuditRecord]) -> Result<()> {
+        if record_list.is_empty() {
+            return Ok(());
+        }
+
+        let mut content = String::new();
+
+        for record in record_list {
+            content.push_str(&serde_json::to_string(record)?);
+            content.push('\n');
+        }
+
+        let _lock = self.lock.lock().await;
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)?;
+
+        file.write_all(content.as_bytes())?;
+        file.sync_data()?;
+
+        Ok(())
+    }
+
+    pub async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let _lock = self.lock.lock().await;
+
+        if !self.file_path.exists() {
+            return Ok(vec![]);
+        }
+
+        let file = fs::File::open(&self.file_path)?;
+
+        let mut record_list = vec![];
+
+        for (line_index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<AuditRecord>(&line) {
+                Ok(record) => {
+                    if query.is_match(&record) {
+                        record_list.push(record);
+                    }
+                }
+                Err(err) => {
+                    warn!("audit log line {} parse error: {:?}", line_index + 1, err);
+                }
+            }
+        }
+
+        if let Some(limit) = query.limit {
+            let skip_len = record_list.len().saturating_sub(limit);
+            record_list.drain(..skip_len);
+        }
+
+        Ok(record_list)
+    }
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::diff::{diff_person, diff_profile};
+use super::model::AuditRecord;
+use crate::auth::model::Identity;
+use crate::person::model::Person;
+use crate::profile::model::Profile;
+use crate::AppData;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use tracing::error;
+
+pub fn new_person_audit_record(
+    identity: &Identity,
+    endpoint: &str,
+    profile_id: u64,
+    before: Option<&Person>,
+    after: &Person,
+) -> AuditRecord {
+    let diff_list = match before {
+        Some(before) => diff_person(before, after),
+        None => vec![String::from("person created")],
+    };
+
+    new_audit_record(identity, endpoint, profile_id, diff_list)
+}
+
+pub fn new_audit_record(
+    identity: &Identity,
+    endpoint: &str,
+    profile_id: u64,
+    diff_list: Vec<String>,
+) -> AuditRecord {
+    AuditRecord {
+        time: Utc::now().timestamp(),
+        user: identity.name.clone(),
+        user_id: identity.user_id,
+        profile_id,
+        endpoint: endpoint.to_string(),
+        diff_list,
+    }
+}
+
+async fn append_audit_record_list(config: &AppData, record_list: &[AuditRecord]) {
+    // 审计日志写入失败不影响存档修改结果
+    if let Err(err) = config.audit_log.append(record_list).await {
+        error!(
+            "append audit log error: {:?}, record_list: {:?}",
+            err, record_list
+        );
+    }
+}
+
+pub async fn audit_change(
+    config: &AppData,
+    identity: &Identity,
+    endpoint: &str,
+    profile_id: u64,
+    diff_list: Vec<String>,
+) {
+    let record = new_audit_record(identity, endpoint, profile_id, diff_list);
+
+    append_audit_record_list(config, &[record]).await;
+}
+
+// 单个存档修改, 无论是否有差异都记录
+pub async fn audit_person_change(
+    config: &AppData,
+    identity: &Identity,
+    endpoint: &str,
+    profile_id: u64,
+    before: Option<&Person>,
+    after: &Person,
+) {
+    let record = new_person_audit_record(identity, endpoint, profile_id, before, after);
+
+    append_audit_record_list(config, &[record]).await;
+}
+
+// 批量修改, 仅记录有差异的存档
+pub async fn audit_person_list_change(
+    config: &AppData,
+    identity: &Identity,
+    endpoint: &str,
+    before_list: &[(u64, Person)],
+    after_list: &[(u64, Person)],
+) {
+    let before_map: HashMap<u64, &Person> = before_list
+        .iter()
+        .map(|(id, person)| (*id, person))
+        .collect();
+
+    let record_list: Vec<AuditRecord> = after_list
+        .iter()
+        .map(|(id, after)| {
+            new_person_audit_record(identity, endpoint, *id, before_map.get(id).copied(), after)
+        })
+        .filter(|record| !record.diff_list.is_empty())
+        .collect();
+
+    append_audit_record_list(config, &record_list).await;
+}
+
+// 批量修改 profile, 仅记录有差异的存档
+pub async fn audit_profile_list_change(
+    config: &AppData,
+    identity: &Identity,
+    endpoint: &str,
+    change_list: &[(u64, Profile, Profile)],
+) {
+    let record_list: Vec<AuditRecord> = change_list
+        .iter()
+        .map(|(id, before, after)| {
+            new_audit_record(identity, endpoint, *id, diff_profile(before, after))
+        })
+        .filter(|record| !record.diff_list.is_empty())
+        .collect();
+
+    append_audit_record_list(config, &record_list).await;
+}
+
+// 批量操作的说明性记录, 如升级回滚
+pub async fn audit_change_list(
+    config: &AppData,
+    identity: &Identity,
+    endpoint: &str,
+    change_list: Vec<(u64, Vec<String>)>,
+) {
+    let record_list: Vec<AuditRecord> = change_list
+        .into_iter()
+        .map(|(id, diff_list)| new_audit_record(identity, endpoint, id, diff_list))
+        .collect();
+
+    append_audit_record_list(config, &record_list).await;
+}
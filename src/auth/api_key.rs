@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{ApiKey, ApiKeyScope, ApiKeys};
+use crate::constant::{API_KEYS_JSON_FILE_NAME, API_KEY_PREFIX, API_KEY_TOUCH_INTERVAL_SECS};
+use crate::utils::write_file_atomic;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::prelude::*;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+use tracing::info;
+
+#[derive(Debug, Default)]
+struct ApiKeyCache {
+    api_keys: Option<ApiKeys>,
+    // 最近一次落盘时间, 用于限制 last_used_at 的写入频率
+    saved_at: i64,
+}
+
+// api_keys.json 的读写入口
+#[derive(Debug)]
+pub struct ApiKeyStore {
+    file_path: PathBuf,
+    cache: Mutex<ApiKeyCache>,
+}
+
+fn random_token(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_secret(secret: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(secret.as_bytes()))
+}
+
+// key 格式: rwr_<id>.<secret>
+fn parse_key(key: &str) -> Result<(&str, &str)> {
+    key.strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+        .ok_or(anyhow!("api key format not correct"))
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+impl ApiKeyCache {
+    fn load(&mut self, file_path: &Path) -> Result<&mut ApiKeys> {
+        if self.api_keys.is_none() {
+            let api_keys = if file_path.exists() {
+                let file = fs::read_to_string(file_path)?;
+                serde_json::from_str(file.trim())?
+            } else {
+                ApiKeys::default()
+            };
+
+            self.api_keys = Some(api_keys);
+        }
+
+        self.api_keys.as_mut().ok_or(anyhow!("api key cache empty"))
+    }
+
+    fn save(&mut self, file_path: &Path) -> Result<()> {
+        let api_keys = self.load(file_path)?;
+        let json_str = serde_json::to_string(api_keys)?;
+
+        write_file_atomic(file_path, json_str.as_bytes())?;
+
+        self.saved_at = Utc::now().timestamp();
+
+        Ok(())
+    }
+}
+
+impl ApiKeyStore {
+    pub fn new(data_path: &str) -> Self {
+        Self {
+            file_path: Path::new(data_path).join(API_KEYS_JSON_FILE_NAME),
+            cache: Mutex::new(ApiKeyCache::default()),
+        }
+    }
+
+    pub async fn query_all(&self) -> Result<Vec<ApiKey>> {
+        let mut cache = self.cache.lock().await;
+
+        Ok(cache.load(&self.file_path)?.api_key_list.clone())
+    }
+
+    // 返回新建的记录与完整 key
+    pub async fn create(
+        &self,
+        name: &str,
+        scope_list: Vec<ApiKeyScope>,
+        created_by: &str,
+    ) -> Result<(ApiKey, String)> {
+        let mut cache = self.cache.lock().await;
+        let api_keys = cache.load(&self.file_path)?;
+
+        if api_keys.api_key_list.iter().any(|x| x.name == name) {
+            return Err(anyhow!("api key name already exists"));
+        }
+
+        let id = random_token(9);
+        let secret = random_token(32);
+
+        let api_key = ApiKey {
+            id: id.clone(),
+            name: name.to_string(),
+            scope_list,
+            secret_hash: hash_secret(&secret),
+            created_at: Utc::now().timestamp(),
+            created_by: created_by.to_string(),
+            last_used_at: None,
+        };
+
+        api_keys.api_key_list.push(api_key.clone());
+        cache.save(&self.file_path)?;
+
+        Ok((api_key, format!("{}{}.{}", API_KEY_PREFIX, id, secret)))
+    }
+
+    pub async fn revoke(&self, id: &str) -> Result<ApiKey> {
+        let mut cache = self.cache.lock().await;
+        let api_keys = cache.load(&self.file_path)?;
+
+        let index = api_keys
+            .api_key_list
+            .iter()
+            .position(|x| x.id == id)
+            .ok_or(anyhow!("api key not found"))?;
+
+        let api_key = api_keys.api_key_list.remove(index);
+        cache.save(&self.file_path)?;
+
+        Ok(api_key)
+    }
+
+    // 校验 key 并记录最后使用时间
+    pub async fn resolve(&self, key: &str) -> Result<ApiKey> {
+        let (id, secret) = parse_key(key)?;
+        let secret_hash = hash_secret(secret);
+
+        let mut cache = self.cache.lock().await;
+        let api_keys = cache.load(&self.file_path)?;
+
+        let api_key = api_keys
+            .api_key_list
+            .iter_mut()
+            .find(|x| x.id == id && x.secret_hash == secret_hash)
+            .ok_or(anyhow!("api key not found"))?;
+
+        let now = Utc::now().timestamp();
+        api_key.last_used_at = Some(now);
+        let api_key = api_key.clone();
+
+        if now - cache.saved_at >= API_KEY_TOUCH_INTERVAL_SECS {
+            info!("api key: {} touch last_used_at", api_key.name);
+            cache.save(&self.file_path)?;
+        }
+
+        Ok(api_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiKeyStore;
+    use crate::auth::model::ApiKeyScope;
+    use crate::constant::API_KEYS_JSON_FILE_NAME;
+    use std::fs;
+
+    #[tokio::test]
+    async fn resolve_by_secret_hash() {
+        let folder = std::env::temp_dir().join(format!("rwr_api_key_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+
+        let store = ApiKeyStore::new(folder.to_str().unwrap());
+        let (api_key, key) = store
+            .create("bot", vec![ApiKeyScope::CacheRead], "admin")
+            .await
+            .unwrap();
+
+        let resolved = store.resolve(&key).await;
+        let wrong_secret = store.resolve(&format!("{}x", key)).await;
+        let wrong_format = store.resolve("rwr_no_secret").await;
+        let file = fs::read_to_string(folder.join(API_KEYS_JSON_FILE_NAME)).unwrap();
+
+        // 重新加载后同样可以校验
+        let reloaded = ApiKeyStore::new(folder.to_str().unwrap())
+            .resolve(&key)
+            .await;
+        fs::remove_dir_all(&folder).unwrap();
+
+        let (_, secret) = key.split_once('.').unwrap();
+
+        assert_eq!(resolved.unwrap().id, api_key.id);
+        assert!(wrong_secret.is_err());
+        assert!(wrong_format.is_err());
+        assert!(reloaded.is_ok());
+        // 文件中只保存 secret 的哈希
+        assert!(!file.contains(secret));
+        assert!(file.contains(&api_key.secret_hash));
+    }
+}
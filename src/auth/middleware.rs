@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::Identity;
+use super::utils::resolve_identity;
+use crate::model::ResponseJson;
+use crate::AppData;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::{header, Method};
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use std::rc::Rc;
+use tracing::warn;
+
+// 无需登录即可调用的非 GET 接口
+static PUBLIC_PATH_LIST: [&str; 3] = ["/user/login", "/user/register", "/user/register_confirm"];
+
+fn get_bearer_token(req: &ServiceRequest) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+
+    value
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+// 解析 Authorization 中的 token 并写入请求上下文, 拒绝匿名调用的修改类接口
+pub struct Authentication;
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            if let (Some(token), Some(config)) =
+                (get_bearer_token(&req), req.app_data::<web::Data<AppData>>())
+            {
+                match resolve_identity(config, &token).await {
+                    Ok(identity) => {
+                        req.extensions_mut().insert(identity);
+                    }
+                    Err(err) => {
+                        warn!(
+                            "resolve identity error: {:?}, path: {}, peer: {:?}",
+                            err,
+                            req.path(),
+                            req.peer_addr()
+                        );
+                    }
+                }
+            }
+
+            let is_anonymous = req.extensions().get::<Identity>().is_none();
+
+            if is_anonymous
+                && req.method() != Method::GET
+                && !PUBLIC_PATH_LIST.contains(&req.path())
+            {
+                let res = HttpResponse::Unauthorized()
+                    .json(ResponseJson::default().set_unauthorized_msg("unauthorized"));
+
+                return Ok(req.into_response(res).map_into_right_body());
+            }
+
+            service.call(req).await.map(|res| res.map_into_left_body())
+        })
+    }
+}
+
+impl FromRequest for Identity {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Identity>().cloned().ok_or_else(|| {
+            InternalError::from_response(
+                "unauthorized",
+                HttpResponse::Unauthorized()
+                    .json(ResponseJson::default().set_unauthorized_msg("unauthorized")),
+            )
+            .into()
+        }))
+    }
+}
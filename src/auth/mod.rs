@@ -0,0 +1,7 @@
+// SPDX-License-Identifier: GPL-3.0-only
+pub mod api_key;
+pub mod middleware;
+pub mod model;
+pub mod service;
+pub mod token;
+pub mod utils;
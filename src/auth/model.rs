@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// 会话签名密钥, Debug 输出时隐藏内容, 避免写入日志
+pub struct SessionSecret(Vec<u8>);
+
+impl SessionSecret {
+    pub fn new(secret: &str) -> Self {
+        Self(secret.as_bytes().to_vec())
+    }
+
+    pub fn random() -> Self {
+        let mut bytes = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SessionSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionSecret(***)")
+    }
+}
+
+// token 载荷
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Claims {
+    // 会话 id, 用于注销
+    pub sid: String,
+    pub user_id: u64,
+    pub name: String,
+    pub admin: u8,
+    // 签发时密码哈希的摘要, 修改密码后已签发的 token 失效
+    #[serde(default)]
+    pub password_stamp: String,
+    // 过期时间戳(秒)
+    pub exp: i64,
+}
+
+// 权限等级, 对应 users.json 中的 admin 字段: 0 玩家, 1 管理员, 2 协管
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Player,
+    Moderator,
+    Admin,
+}
+
+impl From<u8> for Role {
+    fn from(admin: u8) -> Self {
+        match admin {
+            1 => Role::Admin,
+            2 => Role::Moderator,
+            _ => Role::Player,
+        }
+    }
+}
+
+// API key 权限范围
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    // 只读缓存: query_all_cache, query_all_cache_v2, query_ranks
+    CacheRead,
+    // 修改玩家存档
+    PersonWrite,
+    // 修改系统配置
+    SystemWrite,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub scope_list: Vec<ApiKeyScope>,
+    // 仅保存 secret 的 sha256, 明文只在创建时返回一次
+    pub secret_hash: String,
+    pub created_at: i64,
+    pub created_by: String,
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ApiKeys {
+    pub api_key_list: Vec<ApiKey>,
+}
+
+// 返回给前端的 API key 信息, 不包含 secret_hash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyRes {
+    pub id: String,
+    pub name: String,
+    pub scope_list: Vec<ApiKeyScope>,
+    pub created_at: i64,
+    pub created_by: String,
+    pub last_used_at: Option<i64>,
+}
+
+impl From<&ApiKey> for ApiKeyRes {
+    fn from(api_key: &ApiKey) -> Self {
+        Self {
+            id: api_key.id.clone(),
+            name: api_key.name.clone(),
+            scope_list: api_key.scope_list.clone(),
+            created_at: api_key.created_at,
+            created_by: api_key.created_by.clone(),
+            last_used_at: api_key.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyReq {
+    pub name: String,
+    pub scope_list: Vec<ApiKeyScope>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyRes {
+    #[serde(flatten)]
+    pub api_key: ApiKeyRes,
+    // 完整 key, 仅此一次返回
+    pub key: String,
+}
+
+// 请求级别的身份信息, 由中间件解析 token 或 API key 后写入
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub session_id: String,
+    pub user_id: u64,
+    pub name: String,
+    pub admin: u8,
+    pub expire_at: i64,
+    // 通过 API key 调用时为 Some
+    pub scope_list: Option<Vec<ApiKeyScope>>,
+}
+
+impl From<Claims> for Identity {
+    fn from(claims: Claims) -> Self {
+        Self {
+            session_id: claims.sid,
+            user_id: claims.user_id,
+            name: claims.name,
+            admin: claims.admin,
+            expire_at: claims.exp,
+            scope_list: None,
+        }
+    }
+}
+
+impl From<&ApiKey> for Identity {
+    fn from(api_key: &ApiKey) -> Self {
+        Self {
+            session_id: String::new(),
+            user_id: 0,
+            name: format!("api_key:{}", api_key.name),
+            admin: 0,
+            expire_at: 0,
+            scope_list: Some(api_key.scope_list.clone()),
+        }
+    }
+}
+
+impl Identity {
+    pub fn role(&self) -> Role {
+        Role::from(self.admin)
+    }
+
+    pub fn is_api_key(&self) -> bool {
+        self.scope_list.is_some()
+    }
+
+    // API key 不具有任何用户角色
+    pub fn has_role(&self, role: Role) -> bool {
+        !self.is_api_key() && self.role() >= role
+    }
+
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scope_list
+            .as_ref()
+            .is_some_and(|scope_list| scope_list.contains(&scope))
+    }
+
+    // 用户角色满足, 或 API key 拥有对应权限范围
+    pub fn has_role_or_scope(&self, role: Role, scope: ApiKeyScope) -> bool {
+        self.has_role(role) || self.has_scope(scope)
+    }
+
+    // 玩家仅可访问自己绑定的存档, 协管及以上可访问全部
+    pub fn can_access_profile(&self, profile_id: u64) -> bool {
+        (!self.is_api_key() && self.user_id == profile_id)
+            || self.has_role_or_scope(Role::Moderator, ApiKeyScope::PersonWrite)
+    }
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{ApiKeyRes, CreateApiKeyReq, CreateApiKeyRes, Identity, Role};
+use super::utils::check_role;
+use crate::model::ResponseJson;
+use crate::AppData;
+use actix_web::{get, post, web, HttpResponse, Responder};
+use tracing::{error, info, instrument};
+
+pub fn api_key_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api_key")
+            .service(create_api_key)
+            .service(query_all_api_key)
+            .service(revoke_api_key),
+    );
+}
+
+#[instrument]
+#[post("/create")]
+async fn create_api_key(
+    config: web::Data<AppData>,
+    identity: Identity,
+    data: web::Json<CreateApiKeyReq>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let data = data.into_inner();
+
+    if data.name.trim().is_empty() || data.scope_list.is_empty() {
+        return HttpResponse::BadRequest()
+            .json(ResponseJson::default().set_err_msg("name and scope_list required"));
+    }
+
+    return match config
+        .api_key_store
+        .create(data.name.trim(), data.scope_list, &identity.name)
+        .await
+    {
+        Ok((api_key, key)) => {
+            info!(
+                "admin: {} create api key: {}, scope_list: {:?} successful",
+                identity.name, api_key.name, api_key.scope_list
+            );
+
+            HttpResponse::Ok().json(CreateApiKeyRes {
+                api_key: ApiKeyRes::from(&api_key),
+                key,
+            })
+        }
+        Err(err) => {
+            error!("create api key error: {:?}", err);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&err.to_string()))
+        }
+    };
+}
+
+#[instrument]
+#[get("/query_all")]
+async fn query_all_api_key(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    return match config.api_key_store.query_all().await {
+        Ok(api_key_list) => {
+            let res: Vec<ApiKeyRes> = api_key_list.iter().map(ApiKeyRes::from).collect();
+
+            HttpResponse::Ok().json(res)
+        }
+        Err(err) => {
+            error!("query all api key error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("query all api key error"))
+        }
+    };
+}
+
+#[instrument]
+#[post("/revoke/{id}")]
+async fn revoke_api_key(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(String,)>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let id = id.into_inner().0;
+
+    return match config.api_key_store.revoke(&id).await {
+        Ok(api_key) => {
+            info!(
+                "admin: {} revoke api key: {} successful",
+                identity.name, api_key.name
+            );
+
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("revoke api key successful"))
+        }
+        Err(err) => {
+            error!("revoke api key error: {:?}", err);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&err.to_string()))
+        }
+    };
+}
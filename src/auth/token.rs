@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{Claims, SessionSecret};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::prelude::*;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn new_claims(
+    user_id: u64,
+    name: &str,
+    admin: u8,
+    password_stamp: String,
+    expire_secs: u64,
+) -> Claims {
+    let mut sid_bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut sid_bytes);
+
+    Claims {
+        sid: URL_SAFE_NO_PAD.encode(sid_bytes),
+        user_id,
+        name: name.to_string(),
+        admin,
+        password_stamp,
+        exp: Utc::now().timestamp() + expire_secs as i64,
+    }
+}
+
+fn sign(secret: &SessionSecret, payload: &str) -> Result<HmacSha256> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .map_err(|e| anyhow!("init token mac error: {}", e))?;
+    mac.update(payload.as_bytes());
+
+    Ok(mac)
+}
+
+// 密码哈希不直接写入 token, 使用 hmac 摘要代替
+pub fn password_stamp(secret: &SessionSecret, stored_password: &str) -> Result<String> {
+    let stamp = sign(secret, stored_password)?.finalize().into_bytes();
+
+    Ok(URL_SAFE_NO_PAD.encode(stamp))
+}
+
+// token 格式: base64(claims json).base64(hmac-sha256)
+pub fn sign_claims(secret: &SessionSecret, claims: &Claims) -> Result<String> {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_string(claims)?);
+
+    let signature = sign(secret, &payload)?.finalize().into_bytes();
+
+    Ok(format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+pub fn verify_token(secret: &SessionSecret, token: &str) -> Result<Claims> {
+    let (payload, signature) = token.split_once('.').ok_or(anyhow!("token format error"))?;
+
+    let signature = URL_SAFE_NO_PAD.decode(signature)?;
+
+    sign(secret, payload)?
+        .verify_slice(&signature)
+        .map_err(|_| anyhow!("token signature not correct"))?;
+
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+
+    if claims.exp < Utc::now().timestamp() {
+        return Err(anyhow!("token expired"));
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_claims, sign_claims, verify_token};
+    use crate::auth::model::SessionSecret;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::prelude::*;
+
+    #[test]
+    fn verify_signed_token() {
+        let secret = SessionSecret::new("secret");
+        let claims = new_claims(1, "ALICE", 0, String::from("stamp"), 60);
+        let token = sign_claims(&secret, &claims).unwrap();
+
+        let verified = verify_token(&secret, &token).unwrap();
+
+        assert_eq!(verified.sid, claims.sid);
+        assert_eq!(verified.user_id, 1);
+        assert_eq!(verified.admin, 0);
+    }
+
+    #[test]
+    fn reject_forged_token() {
+        let secret = SessionSecret::new("secret");
+        let claims = new_claims(1, "ALICE", 0, String::from("stamp"), 60);
+        let token = sign_claims(&secret, &claims).unwrap();
+
+        // 其他密钥签发的 token
+        assert!(verify_token(&SessionSecret::new("other"), &token).is_err());
+
+        // 修改载荷中的权限, 沿用原签名
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged_claims = new_claims(1, "ALICE", 1, String::from("stamp"), 60);
+        let forged_payload = URL_SAFE_NO_PAD.encode(serde_json::to_string(&forged_claims).unwrap());
+        let forged_token = format!("{}.{}", forged_payload, signature);
+        assert!(verify_token(&secret, &forged_token).is_err());
+
+        assert!(verify_token(&secret, "not a token").is_err());
+    }
+
+    #[test]
+    fn reject_expired_token() {
+        let secret = SessionSecret::new("secret");
+        let mut claims = new_claims(1, "ALICE", 0, String::from("stamp"), 60);
+        claims.exp = Utc::now().timestamp() - 1;
+        let token = sign_claims(&secret, &claims).unwrap();
+
+        assert!(verify_token(&secret, &token).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::api_key::is_api_key;
+use super::model::{ApiKeyScope, Claims, Identity, Role};
+use super::token::{new_claims, password_stamp, sign_claims, verify_token};
+use crate::model::ResponseJson;
+use crate::user::model::User;
+use crate::user::utils::get_user_by_id;
+use crate::AppData;
+use actix_web::HttpResponse;
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use tracing::warn;
+
+pub fn issue_token(config: &AppData, user: &User) -> Result<(String, Claims)> {
+    let claims = new_claims(
+        user.user_id,
+        &user.name,
+        user.admin,
+        password_stamp(&config.session_secret, &user.password)?,
+        config.session_expire_secs,
+    );
+
+    let token = sign_claims(&config.session_secret, &claims)?;
+
+    Ok((token, claims))
+}
+
+pub async fn resolve_identity(config: &AppData, token: &str) -> Result<Identity> {
+    if is_api_key(token) {
+        let api_key = config.api_key_store.resolve(token).await?;
+
+        return Ok(Identity::from(&api_key));
+    }
+
+    let claims = verify_token(&config.session_secret, token)?;
+
+    if config
+        .revoked_session_map
+        .lock()
+        .await
+        .contains_key(&claims.sid)
+    {
+        return Err(anyhow!("session revoked"));
+    }
+
+    // 账号被删除、修改用户名/权限/密码后, 该用户已签发的全部 token 立即失效
+    let user = get_user_by_id(claims.user_id, &mut config.user_store.lock().await)?;
+
+    if user.name != claims.name
+        || user.admin != claims.admin
+        || password_stamp(&config.session_secret, &user.password)? != claims.password_stamp
+    {
+        return Err(anyhow!("account changed"));
+    }
+
+    Ok(Identity::from(claims))
+}
+
+pub async fn revoke_session(config: &AppData, identity: &Identity) {
+    let mut revoked_session_map = config.revoked_session_map.lock().await;
+
+    // 顺带清理已过期的会话, 过期 token 本身已无法通过校验
+    let now = Utc::now().timestamp();
+    revoked_session_map.retain(|_, expire_at| *expire_at >= now);
+
+    revoked_session_map.insert(identity.session_id.clone(), identity.expire_at);
+}
+
+fn forbidden_response(identity: &Identity, reason: &str) -> HttpResponse {
+    warn!(
+        "user: {}, role: {:?} forbidden: {}",
+        identity.name,
+        identity.role(),
+        reason
+    );
+
+    HttpResponse::Forbidden().json(ResponseJson::default().set_forbidden_msg("forbidden"))
+}
+
+pub fn check_role(identity: &Identity, role: Role) -> Result<(), HttpResponse> {
+    if identity.has_role(role) {
+        return Ok(());
+    }
+
+    Err(forbidden_response(
+        identity,
+        &format!("require role {:?}", role),
+    ))
+}
+
+pub fn check_profile_access(identity: &Identity, profile_id: u64) -> Result<(), HttpResponse> {
+    if identity.can_access_profile(profile_id) {
+        return Ok(());
+    }
+
+    Err(forbidden_response(
+        identity,
+        &format!("access profile {}", profile_id),
+    ))
+}
+
+pub fn check_role_or_scope(
+    identity: &Identity,
+    role: Role,
+    scope: ApiKeyScope,
+) -> Result<(), HttpResponse> {
+    if identity.has_role_or_scope(role, scope) {
+        return Ok(());
+    }
+
+    Err(forbidden_response(
+        identity,
+        &format!("require role {:?} or scope {:?}", role, scope),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_profile_access, check_role, check_role_or_scope};
+    use crate::auth::model::{ApiKeyScope, Identity, Role};
+    use actix_web::http::StatusCode;
+
+    fn user_identity(user_id: u64, admin: u8) -> Identity {
+        Identity {
+            session_id: "sid".to_string(),
+            user_id,
+            name: "ALICE".to_string(),
+            admin,
+            expire_at: 0,
+            scope_list: None,
+        }
+    }
+
+    #[test]
+    fn role_ordering() {
+        // users.json 中 admin: 0 玩家, 1 管理员, 2 协管
+        assert_eq!(Role::from(0), Role::Player);
+        assert_eq!(Role::from(1), Role::Admin);
+        assert_eq!(Role::from(2), Role::Moderator);
+        assert_eq!(Role::from(9), Role::Player);
+
+        assert!(Role::Admin > Role::Moderator);
+        assert!(Role::Moderator > Role::Player);
+
+        let admin = user_identity(1, 1);
+        let moderator = user_identity(2, 2);
+        let player = user_identity(3, 0);
+
+        assert!(check_role(&admin, Role::Moderator).is_ok());
+        assert!(check_role(&moderator, Role::Moderator).is_ok());
+        assert!(check_role(&moderator, Role::Admin).is_err());
+        assert!(check_role(&player, Role::Player).is_ok());
+        assert!(check_role(&player, Role::Moderator).is_err());
+
+        // 玩家只能访问自己的存档
+        assert!(check_profile_access(&player, 3).is_ok());
+        assert!(check_profile_access(&player, 4).is_err());
+        assert!(check_profile_access(&moderator, 4).is_ok());
+    }
+
+    #[test]
+    fn role_or_scope() {
+        let moderator = user_identity(2, 2);
+        let player = user_identity(3, 0);
+        let api_key = Identity {
+            scope_list: Some(vec![ApiKeyScope::PersonWrite]),
+            ..user_identity(0, 1)
+        };
+
+        assert!(check_role_or_scope(&moderator, Role::Moderator, ApiKeyScope::PersonWrite).is_ok());
+        assert!(check_role_or_scope(&api_key, Role::Admin, ApiKeyScope::PersonWrite).is_ok());
+
+        // API key 只按权限范围判断, 不具有用户角色
+        assert!(check_role(&api_key, Role::Player).is_err());
+        assert!(check_role_or_scope(&api_key, Role::Player, ApiKeyScope::SystemWrite).is_err());
+
+        let res = check_role_or_scope(&player, Role::Moderator, ApiKeyScope::PersonWrite);
+        assert_eq!(res.unwrap_err().status(), StatusCode::FORBIDDEN);
+    }
+}
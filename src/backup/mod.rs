@@ -0,0 +1,4 @@
+// SPDX-License-Identifier: GPL-3.0-only
+pub mod model;
+pub mod service;
+pub mod store;
@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    Person,
+    Profile,
+}
+
+impl BackupKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BackupKind::Person => "person",
+            BackupKind::Profile => "profile",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupItem {
+    // 备份时间 unix 毫秒时间戳, 同时作为备份 id
+    pub backup_id: u64,
+    pub kind: BackupKind,
+    pub created_at: i64,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupDiffRes {
+    pub backup_id: u64,
+    pub kind: BackupKind,
+    // 由备份到当前存档的差异
+    pub diff_list: Vec<String>,
+}
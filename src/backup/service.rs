@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{BackupDiffRes, BackupKind};
+use crate::audit::diff::{diff_person, diff_profile};
+use crate::audit::utils::audit_change;
+use crate::auth::model::{ApiKeyScope, Identity, Role};
+use crate::auth::utils::{check_profile_access, check_role_or_scope};
+use crate::person::extract::extract_person;
+use crate::profile::extract::extract_profile;
+use crate::xml::error::parse_error_response;
+use crate::{model::ResponseJson, AppData};
+use actix_files::NamedFile;
+use actix_web::error::InternalError;
+use actix_web::{get, post, web, HttpResponse, Responder, Result};
+use tracing::{error, info, instrument};
+
+pub fn backup_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/backup")
+            .service(query_backup)
+            .service(download_backup)
+            .service(diff_backup)
+            .service(restore_backup),
+    );
+}
+
+// 由 from 所在目录到 to 所在目录的存档差异
+fn diff_folder(
+    id: u64,
+    kind: BackupKind,
+    from_folder: &str,
+    to_folder: &str,
+) -> anyhow::Result<Vec<String>> {
+    let diff_list = match kind {
+        BackupKind::Person => diff_person(
+            &extract_person(id, from_folder)?,
+            &extract_person(id, to_folder)?,
+        ),
+        BackupKind::Profile => diff_profile(
+            &extract_profile(id, from_folder)?,
+            &extract_profile(id, to_folder)?,
+        ),
+    };
+
+    Ok(diff_list)
+}
+
+#[instrument]
+#[get("/query/{id}")]
+async fn query_backup(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_profile_access(&identity, id.0) {
+        return res;
+    }
+
+    return match config.backup_store.query(id.0) {
+        Ok(backup_list) => HttpResponse::Ok().json(backup_list),
+        Err(err) => {
+            error!("query backup error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("query backup error"))
+        }
+    };
+}
+
+#[instrument]
+#[get("/download/{id}/{backup_id}/{kind}")]
+async fn download_backup(
+    config: web::Data<AppData>,
+    identity: Identity,
+    path: web::Path<(u64, u64, BackupKind)>,
+) -> Result<NamedFile> {
+    info!("");
+    let (id, backup_id, kind) = path.into_inner();
+
+    check_profile_access(&identity, id)
+        .map_err(|res| InternalError::from_response("forbidden", res))?;
+
+    let file_path = config
+        .backup_store
+        .get_backup_file_path(id, backup_id, kind)
+        .map_err(|err| {
+            error!("download backup error: {:?}", err);
+            InternalError::from_response(
+                "backup not found",
+                HttpResponse::NotFound()
+                    .json(ResponseJson::default().set_err_msg("backup not found")),
+            )
+        })?;
+
+    Ok(NamedFile::open_async(file_path).await?)
+}
+
+#[instrument]
+#[get("/diff/{id}/{backup_id}/{kind}")]
+async fn diff_backup(
+    config: web::Data<AppData>,
+    identity: Identity,
+    path: web::Path<(u64, u64, BackupKind)>,
+) -> impl Responder {
+    info!("");
+    let (id, backup_id, kind) = path.into_inner();
+
+    if let Err(res) = check_profile_access(&identity, id) {
+        return res;
+    }
+
+    let res = config
+        .backup_store
+        .get_backup_folder(id, backup_id, kind)
+        .and_then(|backup_folder| {
+            diff_folder(id, kind, &backup_folder, &config.rwr_profile_folder_path)
+        });
+
+    return match res {
+        Ok(diff_list) => HttpResponse::Ok().json(BackupDiffRes {
+            backup_id,
+            kind,
+            diff_list,
+        }),
+        Err(err) => {
+            error!("diff backup error: {:?}", err);
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest()
+                    .json(ResponseJson::default().set_err_msg(&err.to_string()))
+            })
+        }
+    };
+}
+
+#[instrument]
+#[post("/restore/{id}/{backup_id}/{kind}")]
+async fn restore_backup(
+    config: web::Data<AppData>,
+    identity: Identity,
+    path: web::Path<(u64, u64, BackupKind)>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
+    let (id, backup_id, kind) = path.into_inner();
+
+    let _lock = config.profile_lock_registry.lock(id).await;
+
+    let res = config
+        .backup_store
+        .get_backup_folder(id, backup_id, kind)
+        .and_then(|backup_folder| {
+            // 差异仅用于审计, 当前存档无法解析时不影响恢复
+            let diff_list = diff_folder(id, kind, &config.rwr_profile_folder_path, &backup_folder)
+                .unwrap_or_else(|err| vec![format!("diff error: {}", err)]);
+
+            config
+                .backup_store
+                .restore(&config.rwr_profile_folder_path, id, backup_id, kind)?;
+
+            Ok(diff_list)
+        });
+
+    return match res {
+        Ok(diff_list) => {
+            info!(
+                "user: {} restore {} {:?} from backup {} successful",
+                identity.name, id, kind, backup_id
+            );
+
+            audit_change(&config, &identity, "/backup/restore", id, diff_list).await;
+
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("restore backup successful"))
+        }
+        Err(err) => {
+            error!("restore backup error: {:?}", err);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&err.to_string()))
+        }
+    };
+}
@@ -0,0 +1,224 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{BackupItem, BackupKind};
+use crate::constant::BACKUP_FOLDER_NAME;
+use crate::utils::{copy_file_atomic, write_file_atomic};
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// 修改存档前的自动备份
+// 目录结构: {server_data_folder_path}/backups/{id}/{backup_id}/{id}.person
+#[derive(Debug, Clone)]
+pub struct BackupStore {
+    folder_path: PathBuf,
+    // 每个存档每种文件最多保留的份数, 0 表示不限制
+    max_count: usize,
+    // 最长保留天数, 0 表示不限制
+    max_age_days: u64,
+}
+
+impl BackupStore {
+    pub fn new(data_path: &str, max_count: usize, max_age_days: u64) -> Self {
+        Self {
+            folder_path: Path::new(data_path).join(BACKUP_FOLDER_NAME),
+            max_count,
+            max_age_days,
+        }
+    }
+
+    fn get_profile_folder(&self, id: u64) -> PathBuf {
+        self.folder_path.join(id.to_string())
+    }
+
+    fn get_file_path(&self, id: u64, backup_id: u64, kind: BackupKind) -> PathBuf {
+        self.get_profile_folder(id)
+            .join(backup_id.to_string())
+            .join(format!("{}.{}", id, kind.extension()))
+    }
+
+    // 备份所在目录, 可直接作为 extract_person / extract_profile 的 folder_path
+    pub fn get_backup_folder(&self, id: u64, backup_id: u64, kind: BackupKind) -> Result<String> {
+        let file_path = self.get_file_path(id, backup_id, kind);
+
+        if !file_path.exists() {
+            return Err(anyhow!("backup not found: {}/{}", id, backup_id));
+        }
+
+        file_path
+            .parent()
+            .map(|folder| folder.to_string_lossy().to_string())
+            .ok_or(anyhow!("backup folder error: {:?}", file_path))
+    }
+
+    pub fn get_backup_file_path(
+        &self,
+        id: u64,
+        backup_id: u64,
+        kind: BackupKind,
+    ) -> Result<PathBuf> {
+        let file_path = self.get_file_path(id, backup_id, kind);
+
+        if !file_path.exists() {
+            return Err(anyhow!("backup not found: {}/{}", id, backup_id));
+        }
+
+        Ok(file_path)
+    }
+
+    // 复制当前存档到备份目录, 存档不存在时跳过
+    pub fn backup(&self, profile_folder_path: &str, id: u64, kind: BackupKind) -> Result<()> {
+        let source_path = format!("{}/{}.{}", profile_folder_path, id, kind.extension());
+
+        if !Path::new(&source_path).exists() {
+            return Ok(());
+        }
+
+        // 同一毫秒内多次备份时顺延
+        let mut backup_id = Utc::now().timestamp_millis() as u64;
+        while self.get_file_path(id, backup_id, kind).exists() {
+            backup_id += 1;
+        }
+
+        let target_path = self.get_file_path(id, backup_id, kind);
+
+        if let Some(folder) = target_path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        copy_file_atomic(&source_path, &target_path)?;
+
+        info!("backup {} to {:?}", source_path, target_path);
+
+        if let Err(err) = self.prune(id, kind) {
+            warn!("prune backup {} error: {:?}", id, err);
+        }
+
+        Ok(())
+    }
+
+    // 使用指定备份覆盖当前存档, 覆盖前同样备份当前存档, 以便撤销本次恢复
+    // 备份当前存档时可能清理掉该备份, 需先读取备份内容
+    pub fn restore(
+        &self,
+        profile_folder_path: &str,
+        id: u64,
+        backup_id: u64,
+        kind: BackupKind,
+    ) -> Result<()> {
+        let content = fs::read(self.get_backup_file_path(id, backup_id, kind)?)?;
+
+        self.backup(profile_folder_path, id, kind)
+            .map_err(|err| anyhow!("backup current error: {}", err))?;
+
+        let target_path = format!("{}/{}.{}", profile_folder_path, id, kind.extension());
+
+        write_file_atomic(target_path, &content)
+    }
+
+    // 按时间倒序返回该存档的全部备份
+    pub fn query(&self, id: u64) -> Result<Vec<BackupItem>> {
+        let profile_folder = self.get_profile_folder(id);
+
+        if !profile_folder.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut backup_list = vec![];
+
+        for entry in fs::read_dir(profile_folder)? {
+            let entry = entry?;
+
+            let backup_id: u64 = match entry.file_name().to_string_lossy().parse() {
+                Ok(backup_id) => backup_id,
+                Err(_) => continue,
+            };
+
+            for kind in [BackupKind::Person, BackupKind::Profile] {
+                let file_path = self.get_file_path(id, backup_id, kind);
+
+                if let Ok(metadata) = fs::metadata(&file_path) {
+                    backup_list.push(BackupItem {
+                        backup_id,
+                        kind,
+                        created_at: (backup_id / 1000) as i64,
+                        size: metadata.len(),
+                    });
+                }
+            }
+        }
+
+        backup_list.sort_by_key(|item| Reverse(item.backup_id));
+
+        Ok(backup_list)
+    }
+
+    fn prune(&self, id: u64, kind: BackupKind) -> Result<()> {
+        let expire_at = Utc::now().timestamp() - (self.max_age_days * 60 * 60 * 24) as i64;
+
+        let expired_list = self
+            .query(id)?
+            .into_iter()
+            .filter(|item| item.kind == kind)
+            .enumerate()
+            .filter(|(index, item)| {
+                (self.max_count > 0 && *index >= self.max_count)
+                    || (self.max_age_days > 0 && item.created_at < expire_at)
+            });
+
+        for (_, item) in expired_list {
+            let file_path = self.get_file_path(id, item.backup_id, kind);
+
+            fs::remove_file(&file_path)?;
+
+            // 同一备份目录下无其他文件时一并删除
+            if let Some(folder) = file_path.parent() {
+                if fs::read_dir(folder)?.next().is_none() {
+                    fs::remove_dir(folder)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackupStore;
+    use crate::backup::model::BackupKind;
+    use std::fs;
+
+    #[test]
+    fn restore_oldest_backup() {
+        let folder = std::env::temp_dir().join(format!("rwr_backup_{}", std::process::id()));
+        let profile_folder = folder.join("profiles");
+        fs::create_dir_all(&profile_folder).unwrap();
+
+        let profile_folder_path = profile_folder.to_str().unwrap();
+        let backup_store = BackupStore::new(folder.join("data").to_str().unwrap(), 3, 0);
+
+        for index in 0..4 {
+            fs::write(profile_folder.join("1.person"), format!("v{}", index)).unwrap();
+            backup_store
+                .backup(profile_folder_path, 1, BackupKind::Person)
+                .unwrap();
+        }
+
+        // 已达到保留上限, 恢复最旧的备份时会先清理掉它
+        let backup_list = backup_store.query(1).unwrap();
+        let oldest = backup_list.last().unwrap().backup_id;
+
+        let res = backup_store.restore(profile_folder_path, 1, oldest, BackupKind::Person);
+        let content = fs::read_to_string(profile_folder.join("1.person")).unwrap();
+        let backup_count = backup_store.query(1).unwrap().len();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(backup_list.len(), 3);
+        assert!(res.is_ok());
+        assert_eq!(content, "v1");
+        assert_eq!(backup_count, 3);
+    }
+}
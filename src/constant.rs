@@ -3,7 +3,41 @@ pub static MAX_DEFAULT_BACKPACK_LEN: u16 = 255;
 pub static MAX_DEFAULT_STASH_LEN: u16 = 300;
 pub static MAX_PERSON_FILE_VERSION: u16 = 154;
 pub static MAX_PROFILE_FILE_VERSION: u16 = 154;
+// 小队标签: 最长 10 个字符, 仅允许字母, 数字及以下符号
+pub static MAX_SQUAD_TAG_LEN: usize = 10;
+pub static SQUAD_TAG_SYMBOL_CHARS: &'static str = "[]()-_.|*#!+=~";
+// 登录会话默认有效期: 1 天
+pub static DEFAULT_SESSION_EXPIRE_SECS: u64 = 60 * 60 * 24;
+
+// 登录失败限流默认值: 连续失败 5 次锁定 15 分钟, 之前每次失败等待 1, 2, 4, 8 秒
+pub static DEFAULT_LOGIN_MAX_FAILURES: u32 = 5;
+pub static DEFAULT_LOGIN_BACKOFF_BASE_SECS: u64 = 1;
+pub static DEFAULT_LOGIN_LOCKOUT_SECS: u64 = 60 * 15;
+// 注册验证码: 有效期 30 分钟, 长度 6, 确认失败 5 次后作废
+pub static REGISTER_CHALLENGE_EXPIRE_SECS: i64 = 60 * 30;
+pub static REGISTER_CODE_LEN: usize = 6;
+pub static REGISTER_MAX_FAILURES: u32 = 5;
+// 存档备份默认保留: 每个存档最多 20 份, 最长 30 天
+pub static DEFAULT_BACKUP_MAX_COUNT: usize = 20;
+pub static DEFAULT_BACKUP_MAX_AGE_DAYS: u64 = 30;
+// 存档目录快照默认保留: 24 份每小时, 7 份每天, 4 份每周, 手动触发最多 10 份
+pub static DEFAULT_SNAPSHOT_HOURLY_COUNT: usize = 24;
+pub static DEFAULT_SNAPSHOT_DAILY_COUNT: usize = 7;
+pub static DEFAULT_SNAPSHOT_WEEKLY_COUNT: usize = 4;
+pub static MAX_MANUAL_SNAPSHOT_COUNT: usize = 10;
+// API key 前缀, 用于在 Authorization 中区分 API key 与会话 token
+pub static API_KEY_PREFIX: &'static str = "rwr_";
+// API key 最后使用时间的落盘间隔
+pub static API_KEY_TOUCH_INTERVAL_SECS: i64 = 60;
 
 pub static USERS_JSON_FILE_NAME: &'static str = "users.json";
+pub static API_KEYS_JSON_FILE_NAME: &'static str = "api_keys.json";
+pub static AUDIT_LOG_FILE_NAME: &'static str = "audit.jsonl";
+pub static BACKUP_FOLDER_NAME: &'static str = "backups";
+pub static SNAPSHOT_FOLDER_NAME: &'static str = "snapshots";
+pub static QUARANTINE_FOLDER_NAME: &'static str = "quarantine";
+pub static MIGRATION_REPORT_FILE_NAME: &'static str = "migration_report.json";
+pub static MIGRATION_FOLDER_NAME: &'static str = "migrations";
+pub static MIGRATION_JSON_FILE_NAME: &'static str = "migration.json";
 pub static QUICK_ITEMS_JSON_FILE_NAME: &'static str = "quick_items.json";
 pub static RANKS_JSON_FILE_NAME: &'static str = "ranks.json";
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use crate::backup::model::BackupKind;
+use crate::xml::error::ParseError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    PersonMissing,
+    ProfileMissing,
+    // xml 格式或属性值错误, 文件读取失败
+    Unparsable,
+    VersionMismatch,
+    // 背包或仓库物品数超出容量
+    OverCapacity,
+}
+
+impl HealthStatus {
+    pub fn from_parse_error(kind: BackupKind, err: &ParseError) -> Self {
+        match err {
+            ParseError::NotFound { .. } => match kind {
+                BackupKind::Person => HealthStatus::PersonMissing,
+                BackupKind::Profile => HealthStatus::ProfileMissing,
+            },
+            ParseError::UnsupportedVersion { .. } => HealthStatus::VersionMismatch,
+            _ => HealthStatus::Unparsable,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthIssue {
+    pub kind: BackupKind,
+    pub status: HealthStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthItem {
+    pub id: u64,
+    // 无问题时为 ok, 否则为第一个问题的类型
+    pub status: HealthStatus,
+    pub issue_list: Vec<HealthIssue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthReport {
+    pub scanned_at: i64,
+    pub total: usize,
+    pub ok_count: usize,
+    // 仅包含存在问题的存档
+    pub item_list: Vec<HealthItem>,
+    // 文件名无法解析为 id 的存档文件
+    pub invalid_name_list: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuarantineItem {
+    pub id: u64,
+    pub kind: BackupKind,
+    // 隔离时间 unix 毫秒时间戳
+    pub quarantined_at: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreHealthRes {
+    pub id: u64,
+    pub kind: BackupKind,
+    // 用于恢复的备份 id
+    pub backup_id: u64,
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::RestoreHealthRes;
+use super::utils::{restore_latest_valid_backup, scan_profile_folder};
+use crate::audit::utils::audit_change;
+use crate::auth::model::{Identity, Role};
+use crate::auth::utils::check_role;
+use crate::backup::model::BackupKind;
+use crate::{model::ResponseJson, AppData};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use tracing::{error, info, instrument};
+
+pub fn health_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/health")
+            .service(scan_health)
+            .service(quarantine_profile)
+            .service(query_all_quarantine)
+            .service(restore_profile),
+    );
+}
+
+#[instrument]
+#[get("/scan")]
+async fn scan_health(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let cloned_folder_path = config.rwr_profile_folder_path.clone();
+    return match scan_profile_folder(cloned_folder_path).await {
+        Ok(report) => {
+            info!(
+                "health scan total: {}, ok: {}",
+                report.total, report.ok_count
+            );
+            HttpResponse::Ok().json(report)
+        }
+        Err(err) => {
+            error!("health scan error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("health scan error"))
+        }
+    };
+}
+
+#[instrument]
+#[post("/quarantine/{id}/{kind}")]
+async fn quarantine_profile(
+    config: web::Data<AppData>,
+    identity: Identity,
+    path: web::Path<(u64, BackupKind)>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let (id, kind) = path.into_inner();
+
+    let _lock = config.profile_lock_registry.lock(id).await;
+
+    return match config
+        .quarantine_store
+        .quarantine(&config.rwr_profile_folder_path, id, kind)
+    {
+        Ok(item) => {
+            info!(
+                "admin: {} quarantine {} {:?} successful",
+                identity.name, id, kind
+            );
+
+            audit_change(
+                &config,
+                &identity,
+                "/health/quarantine",
+                id,
+                vec![format!(
+                    "quarantine {}: {}",
+                    kind.extension(),
+                    item.quarantined_at
+                )],
+            )
+            .await;
+
+            HttpResponse::Ok().json(item)
+        }
+        Err(err) => {
+            error!("quarantine error: {:?}", err);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&err.to_string()))
+        }
+    };
+}
+
+#[instrument]
+#[get("/quarantine/query_all")]
+async fn query_all_quarantine(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    return match config.quarantine_store.query_all() {
+        Ok(quarantine_list) => HttpResponse::Ok().json(quarantine_list),
+        Err(err) => {
+            error!("query all quarantine error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("query all quarantine error"))
+        }
+    };
+}
+
+#[instrument]
+#[post("/restore/{id}/{kind}")]
+async fn restore_profile(
+    config: web::Data<AppData>,
+    identity: Identity,
+    path: web::Path<(u64, BackupKind)>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let (id, kind) = path.into_inner();
+
+    let _lock = config.profile_lock_registry.lock(id).await;
+
+    return match restore_latest_valid_backup(&config, id, kind) {
+        Ok(backup_id) => {
+            info!(
+                "admin: {} restore {} {:?} from backup {} successful",
+                identity.name, id, kind, backup_id
+            );
+
+            audit_change(
+                &config,
+                &identity,
+                "/health/restore",
+                id,
+                vec![format!("restore {}: {}", kind.extension(), backup_id)],
+            )
+            .await;
+
+            HttpResponse::Ok().json(RestoreHealthRes {
+                id,
+                kind,
+                backup_id,
+            })
+        }
+        Err(err) => {
+            error!("restore from backup error: {:?}", err);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&err.to_string()))
+        }
+    };
+}
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::QuarantineItem;
+use crate::backup::model::BackupKind;
+use crate::constant::QUARANTINE_FOLDER_NAME;
+use crate::utils::copy_file_atomic;
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+// 从存档目录移出的损坏存档, 不自动清理
+// 目录结构: {server_data_folder_path}/quarantine/{id}/{quarantined_at}/{id}.person
+#[derive(Debug, Clone)]
+pub struct QuarantineStore {
+    folder_path: PathBuf,
+}
+
+impl QuarantineStore {
+    pub fn new(data_path: &str) -> Self {
+        Self {
+            folder_path: Path::new(data_path).join(QUARANTINE_FOLDER_NAME),
+        }
+    }
+
+    fn get_file_path(&self, id: u64, quarantined_at: u64, kind: BackupKind) -> PathBuf {
+        self.folder_path
+            .join(id.to_string())
+            .join(quarantined_at.to_string())
+            .join(format!("{}.{}", id, kind.extension()))
+    }
+
+    // 将存档移动到隔离目录, 游戏服务端与查询接口不再读取该文件
+    pub fn quarantine(
+        &self,
+        profile_folder_path: &str,
+        id: u64,
+        kind: BackupKind,
+    ) -> Result<QuarantineItem> {
+        let source_path = format!("{}/{}.{}", profile_folder_path, id, kind.extension());
+
+        if !Path::new(&source_path).exists() {
+            return Err(anyhow!("file not found: {}", source_path));
+        }
+
+        let mut quarantined_at = Utc::now().timestamp_millis() as u64;
+        while self.get_file_path(id, quarantined_at, kind).exists() {
+            quarantined_at += 1;
+        }
+
+        let target_path = self.get_file_path(id, quarantined_at, kind);
+
+        if let Some(folder) = target_path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        // 隔离目录可能与存档目录不在同一文件系统, 复制后再删除
+        copy_file_atomic(&source_path, &target_path)?;
+        fs::remove_file(&source_path)?;
+
+        info!("quarantine {} to {:?}", source_path, target_path);
+
+        Ok(QuarantineItem {
+            id,
+            kind,
+            quarantined_at,
+            size: fs::metadata(&target_path)?.len(),
+        })
+    }
+
+    // 按时间倒序返回全部隔离存档
+    pub fn query_all(&self) -> Result<Vec<QuarantineItem>> {
+        if !self.folder_path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut quarantine_list = vec![];
+
+        for id_entry in fs::read_dir(&self.folder_path)? {
+            let id_entry = id_entry?;
+
+            let id: u64 = match id_entry.file_name().to_string_lossy().parse() {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+
+            for entry in fs::read_dir(id_entry.path())? {
+                let entry = entry?;
+
+                let quarantined_at: u64 = match entry.file_name().to_string_lossy().parse() {
+                    Ok(quarantined_at) => quarantined_at,
+                    Err(_) => continue,
+                };
+
+                for kind in [BackupKind::Person, BackupKind::Profile] {
+                    let file_path = self.get_file_path(id, quarantined_at, kind);
+
+                    if let Ok(metadata) = fs::metadata(&file_path) {
+                        quarantine_list.push(QuarantineItem {
+                            id,
+                            kind,
+                            quarantined_at,
+                            size: metadata.len(),
+                        });
+                    }
+                }
+            }
+        }
+
+        quarantine_list.sort_by_key(|item| Reverse(item.quarantined_at));
+
+        Ok(quarantine_list)
+    }
+}
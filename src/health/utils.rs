@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{HealthIssue, HealthItem, HealthReport, HealthStatus};
+use crate::backup::model::BackupKind;
+use crate::person::async_extract::async_scan_all_id;
+use crate::person::extract::extract_person;
+use crate::profile::extract::extract_profile;
+use crate::AppData;
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use std::collections::BTreeSet;
+
+fn check_person(folder_path: &str, id: u64) -> Option<HealthIssue> {
+    let person = match extract_person(id, folder_path) {
+        Ok(person) => person,
+        Err(err) => {
+            return Some(HealthIssue {
+                kind: BackupKind::Person,
+                status: HealthStatus::from_parse_error(BackupKind::Person, &err),
+                message: err.to_string(),
+            })
+        }
+    };
+
+    let backpack_len = person.backpack_item_list.len();
+    let stash_len = person.stash_item_list.len();
+
+    if backpack_len > usize::from(person.backpack_hard_capacity)
+        || stash_len > usize::from(person.stash_hard_capacity)
+    {
+        return Some(HealthIssue {
+            kind: BackupKind::Person,
+            status: HealthStatus::OverCapacity,
+            message: format!(
+                "backpack: {}/{}, stash: {}/{}",
+                backpack_len, person.backpack_hard_capacity, stash_len, person.stash_hard_capacity
+            ),
+        });
+    }
+
+    None
+}
+
+fn check_profile(folder_path: &str, id: u64) -> Option<HealthIssue> {
+    extract_profile(id, folder_path)
+        .err()
+        .map(|err| HealthIssue {
+            kind: BackupKind::Profile,
+            status: HealthStatus::from_parse_error(BackupKind::Profile, &err),
+            message: err.to_string(),
+        })
+}
+
+pub fn check_health(folder_path: &str, id: u64) -> HealthItem {
+    let issue_list: Vec<HealthIssue> = [
+        check_person(folder_path, id),
+        check_profile(folder_path, id),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    HealthItem {
+        id,
+        status: issue_list
+            .first()
+            .map(|issue| issue.status)
+            .unwrap_or(HealthStatus::Ok),
+        issue_list,
+    }
+}
+
+// 扫描存档目录中全部 id, 只要存在 .person 或 .profile 之一即参与检查
+pub async fn scan_profile_folder(folder_path: String) -> Result<HealthReport> {
+    let (person_id_list, mut invalid_name_list) =
+        async_scan_all_id(&folder_path, ".person").await?;
+    let (profile_id_list, profile_invalid_name_list) =
+        async_scan_all_id(&folder_path, ".profile").await?;
+    invalid_name_list.extend(profile_invalid_name_list);
+
+    let mut id_set: BTreeSet<u64> = person_id_list.into_iter().collect();
+    id_set.extend(profile_id_list);
+
+    let item_future_vec = id_set
+        .iter()
+        .map(|&id| {
+            let cloned_folder_path = folder_path.clone();
+
+            tokio::spawn(async move { check_health(&cloned_folder_path, id) })
+        })
+        .collect::<Vec<_>>();
+
+    let item_vec = futures::future::try_join_all(item_future_vec).await?;
+
+    let total = item_vec.len();
+    let item_list: Vec<HealthItem> = item_vec
+        .into_iter()
+        .filter(|item| item.status != HealthStatus::Ok)
+        .collect();
+
+    Ok(HealthReport {
+        scanned_at: Utc::now().timestamp(),
+        total,
+        ok_count: total - item_list.len(),
+        item_list,
+        invalid_name_list,
+    })
+}
+
+fn is_valid_backup(folder_path: &str, id: u64, kind: BackupKind) -> bool {
+    match kind {
+        BackupKind::Person => extract_person(id, folder_path).is_ok(),
+        BackupKind::Profile => extract_profile(id, folder_path).is_ok(),
+    }
+}
+
+// 使用最近一份可正常解析的备份恢复存档, 返回所用的备份 id
+pub fn restore_latest_valid_backup(config: &AppData, id: u64, kind: BackupKind) -> Result<u64> {
+    let backup_id = config
+        .backup_store
+        .query(id)?
+        .into_iter()
+        .filter(|item| item.kind == kind)
+        .find(|item| {
+            config
+                .backup_store
+                .get_backup_folder(id, item.backup_id, kind)
+                .map(|backup_folder| is_valid_backup(&backup_folder, id, kind))
+                .unwrap_or(false)
+        })
+        .map(|item| item.backup_id)
+        .ok_or(anyhow!("no valid backup found: {} {:?}", id, kind))?;
+
+    config
+        .backup_store
+        .restore(&config.rwr_profile_folder_path, id, backup_id, kind)?;
+
+    Ok(backup_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_health, scan_profile_folder};
+    use crate::health::model::HealthStatus;
+    use crate::xml::extract::read_fixture_list;
+    use std::fs;
+
+    #[test]
+    fn check_health_status() {
+        let person = &read_fixture_list("person")[0].1;
+        let profile = &read_fixture_list("profile")[0].1;
+
+        let folder = std::env::temp_dir().join(format!("rwr_health_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+
+        let file_list = [
+            (1, Some(person.clone()), Some(profile.clone())),
+            (2, None, Some(profile.clone())),
+            (3, Some(person.clone()), None),
+            (
+                4,
+                Some(person.replace("<person ", "<person<")),
+                Some(profile.clone()),
+            ),
+            (
+                5,
+                Some(person.replace(r#"version="154""#, r#"version="152""#)),
+                Some(profile.clone()),
+            ),
+            (
+                6,
+                Some(person.replace(
+                    r#"<backpack hard_capacity="255""#,
+                    r#"<backpack hard_capacity="0""#,
+                )),
+                Some(profile.clone()),
+            ),
+        ];
+
+        for (id, person, profile) in &file_list {
+            if let Some(person) = person {
+                fs::write(folder.join(format!("{}.person", id)), person).unwrap();
+            }
+            if let Some(profile) = profile {
+                fs::write(folder.join(format!("{}.profile", id)), profile).unwrap();
+            }
+        }
+
+        let status_list: Vec<HealthStatus> = file_list
+            .iter()
+            .map(|(id, _, _)| check_health(folder.to_str().unwrap(), *id).status)
+            .collect();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            status_list,
+            [
+                HealthStatus::Ok,
+                HealthStatus::PersonMissing,
+                HealthStatus::ProfileMissing,
+                HealthStatus::Unparsable,
+                HealthStatus::VersionMismatch,
+                HealthStatus::OverCapacity,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_invalid_file_name() {
+        let person = &read_fixture_list("person")[0].1;
+        let profile = &read_fixture_list("profile")[0].1;
+
+        let folder = std::env::temp_dir().join(format!("rwr_health_scan_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("1.person"), person).unwrap();
+        fs::write(folder.join("1.profile"), profile).unwrap();
+        fs::write(folder.join("foo.person"), person).unwrap();
+        fs::write(folder.join("1.bak.profile"), profile).unwrap();
+
+        let report = scan_profile_folder(folder.to_str().unwrap().to_string()).await;
+        fs::remove_dir_all(&folder).unwrap();
+        let report = report.unwrap();
+
+        assert_eq!((report.total, report.ok_count), (1, 1));
+        assert_eq!(report.invalid_name_list, ["foo.person", "1.bak.profile"]);
+    }
+}
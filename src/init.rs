@@ -5,6 +5,10 @@ use std::fs;
 use tracing::{error, info};
 
 static CONFIG_FILE_PATH: &str = "config.json";
+// 命令行参数: 生成存档升级报告后退出, 不修改存档
+pub static MIGRATE_DRY_RUN_FLAG: &str = "--migrate-dry-run";
+// 命令行参数: 回滚最近一次存档升级后退出
+pub static MIGRATE_ROLLBACK_FLAG: &str = "--migrate-rollback";
 
 pub fn init_config() -> Result<Config> {
     info!("Loading config file");
@@ -13,3 +17,7 @@ pub fn init_config() -> Result<Config> {
         anyhow::anyhow!("init config err: {}", e)
     })
 }
+
+pub fn has_cli_flag(flag: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{
+    Mutex as AsyncMutex, OwnedMutexGuard, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock,
+};
+
+// 持有期间其他请求无法修改对应存档, 全量快照需等待所有持有者释放
+pub struct ProfileLockGuard {
+    _guard_list: Vec<OwnedMutexGuard<()>>,
+    _gate: OwnedRwLockReadGuard<()>,
+}
+
+pub type ProfileFolderLockGuard = OwnedRwLockWriteGuard<()>;
+
+// 按存档 id 加锁, 同一存档的 读取 -> 修改 -> 保存 需持有对应锁
+// 避免并发修改互相覆盖
+// 锁数量上限为存档数量, 不做回收
+#[derive(Debug, Default)]
+pub struct ProfileLockRegistry {
+    lock_map: Mutex<HashMap<u64, Arc<AsyncMutex<()>>>>,
+    // 存档目录级别的读写锁: 单个存档修改持有读锁, 全量快照持有写锁
+    gate: Arc<RwLock<()>>,
+}
+
+impl ProfileLockRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_lock(&self, id: u64) -> Arc<AsyncMutex<()>> {
+        let mut lock_map = self.lock_map.lock().unwrap();
+
+        lock_map.entry(id).or_default().clone()
+    }
+
+    pub async fn lock(&self, id: u64) -> ProfileLockGuard {
+        self.lock_list(&[id]).await
+    }
+
+    // 批量加锁: 去重后按 id 升序依次获取
+    // 保证任意两个批量操作的加锁顺序一致, 不会死锁
+    pub async fn lock_list(&self, id_list: &[u64]) -> ProfileLockGuard {
+        let gate = self.gate.clone().read_owned().await;
+
+        let mut sorted_id_list = id_list.to_vec();
+        sorted_id_list.sort_unstable();
+        sorted_id_list.dedup();
+
+        let mut guard_list = Vec::with_capacity(sorted_id_list.len());
+
+        for id in sorted_id_list {
+            guard_list.push(self.get_lock(id).lock_owned().await);
+        }
+
+        ProfileLockGuard {
+            _guard_list: guard_list,
+            _gate: gate,
+        }
+    }
+
+    // 锁定整个存档目录, 等待进行中的修改全部完成
+    pub async fn lock_folder(&self) -> ProfileFolderLockGuard {
+        self.gate.clone().write_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProfileLockRegistry;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn lock_loop(registry: Arc<ProfileLockRegistry>, id_list: Vec<u64>) {
+        for _ in 0..1000 {
+            let _guard = registry.lock_list(&id_list).await;
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn lock_list_overlap_no_deadlock() {
+        let registry = Arc::new(ProfileLockRegistry::new());
+
+        let task_a = tokio::spawn(lock_loop(registry.clone(), vec![1, 2, 3]));
+        let task_b = tokio::spawn(lock_loop(registry.clone(), vec![3, 2, 1, 2]));
+
+        let result = tokio::time::timeout(Duration::from_secs(10), async {
+            task_a.await.unwrap();
+            task_b.await.unwrap();
+        })
+        .await;
+
+        assert!(result.is_ok(), "lock_list deadlocked");
+    }
+
+    #[tokio::test]
+    async fn lock_folder_waits_for_profile_lock() {
+        let registry = ProfileLockRegistry::new();
+
+        let guard = registry.lock(1).await;
+        let waiting = tokio::time::timeout(Duration::from_millis(50), registry.lock_folder()).await;
+        assert!(waiting.is_err());
+
+        drop(guard);
+        let _folder_guard = registry.lock_folder().await;
+    }
+}
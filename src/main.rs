@@ -1,31 +1,52 @@
 // SPDX-License-Identifier: GPL-3.0-only
+use crate::audit::store::AuditLog;
+use crate::auth::{
+    api_key::ApiKeyStore, middleware::Authentication, model::SessionSecret, service::api_key_config,
+};
+use crate::backup::{service::backup_config, store::BackupStore};
+use crate::health::{service::health_config, store::QuarantineStore};
+use crate::lock::ProfileLockRegistry;
 use crate::model::AppData;
 use crate::person::{async_extract::async_extract_query_data, service::person_config};
 use crate::profile::service::profile_config;
+use crate::snapshot::{
+    service::snapshot_config, store::SnapshotStore, utils::create_scheduled_snapshot,
+};
 use crate::system::service::system_config;
-use crate::user::service::user_config;
-use crate::version_update::preupdate::preupdate;
+use crate::user::{service::user_config, store::UserStore, throttle::LoginThrottle};
+use crate::version_update::{
+    preupdate::preupdate, service::migration_config, store::MigrationStore,
+};
 use actix_web::{web, App, HttpServer};
 use anyhow::{Error, Result};
 use chrono::prelude::*;
+use std::collections::HashMap;
 use tokio;
 use tokio::{
     sync::Mutex,
     time::{interval, Duration},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_appender::rolling;
 use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
+mod audit;
+mod auth;
+mod backup;
 mod constant;
+mod health;
 mod init;
+mod lock;
 mod model;
 mod person;
+mod ping;
 mod profile;
+mod snapshot;
 mod system;
 mod user;
+mod utils;
 mod version_update;
-mod ping;
+mod xml;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -33,18 +54,59 @@ async fn main() -> Result<()> {
 
     let server_log_folder_path = config.server_log_folder_path.clone();
 
+    let session_secret_is_random = config.session_secret.is_empty();
+    let session_secret = if session_secret_is_random {
+        SessionSecret::random()
+    } else {
+        SessionSecret::new(&config.session_secret)
+    };
+
+    let user_store = UserStore::new(&config.server_data_folder_path);
+    let api_key_store = ApiKeyStore::new(&config.server_data_folder_path);
+    let audit_log = AuditLog::new(&config.server_data_folder_path);
+    let backup_store = BackupStore::new(
+        &config.server_data_folder_path,
+        config.backup_max_count,
+        config.backup_max_age_days,
+    );
+    let snapshot_store = SnapshotStore::new(
+        &config.server_data_folder_path,
+        config.snapshot_hourly_count,
+        config.snapshot_daily_count,
+        config.snapshot_weekly_count,
+    );
+    let quarantine_store = QuarantineStore::new(&config.server_data_folder_path);
+    let migration_store = MigrationStore::new(&config.server_data_folder_path);
+
     let app_data = web::Data::new(AppData {
         server_data_folder_path: config.server_data_folder_path,
         rwr_profile_folder_path: config.rwr_profile_folder_path,
         server_log_folder_path: config.server_log_folder_path,
         server_upload_temp_folder_path: config.server_upload_temp_folder_path,
-        user_json_lock: Mutex::new(0),
+        user_store,
+        login_throttle: LoginThrottle::new(
+            config.login_max_failures,
+            config.login_backoff_base_secs,
+            config.login_lockout_secs,
+        ),
         // hourly query_all
         snapshot_data: Mutex::new(vec![]),
         snapshot_str: Mutex::new(String::new()),
         snapshot_time: Mutex::new(String::new()),
         // ranks snapshot
         snapshot_ranks: Mutex::new(vec![]),
+        // session
+        session_secret,
+        session_expire_secs: config.session_expire_secs,
+        revoked_session_map: Mutex::new(HashMap::new()),
+        register_challenge_map: Mutex::new(HashMap::new()),
+        api_key_store,
+        audit_log,
+        profile_lock_registry: ProfileLockRegistry::new(),
+        backup_store,
+        snapshot_store,
+        quarantine_store,
+        migration_store,
     });
 
     let std_out_layer = tracing_subscriber::fmt::layer()
@@ -65,18 +127,45 @@ async fn main() -> Result<()> {
             .with(fmt_layer)
             .init();
     } else {
-        tracing_subscriber::registry()
-            .with(std_out_layer)
-            .init();
+        tracing_subscriber::registry().with(std_out_layer).init();
     }
 
     info!("completed reading app_data: {:?}", app_data);
 
+    if session_secret_is_random {
+        warn!(
+            "session_secret is empty, using random secret, all sessions will expire after restart"
+        );
+    }
+
     let app_data_c = app_data.clone();
 
+    // 回滚最近一次存档升级后退出, 不再执行升级
+    if init::has_cli_flag(init::MIGRATE_ROLLBACK_FLAG) {
+        let item = app_data_c.migration_store.rollback_last(
+            &app_data_c.rwr_profile_folder_path,
+            &app_data_c.backup_store,
+        )?;
+        info!("migration {} rolled back, exit", item.migration_id);
+        return Ok(());
+    }
+
     // update file version
     let folder_path = app_data_c.rwr_profile_folder_path.clone();
-    preupdate(folder_path).await?;
+    // dry-run 时存档未升级, 不能继续提供服务, 生成报告后退出
+    let dry_run = config.migrate_dry_run || init::has_cli_flag(init::MIGRATE_DRY_RUN_FLAG);
+    preupdate(
+        folder_path,
+        &app_data_c.server_data_folder_path,
+        dry_run,
+        &app_data_c.migration_store,
+    )
+    .await?;
+
+    if dry_run {
+        info!("migrate dry run completed, exit");
+        return Ok(());
+    }
 
     if config.server_hourly_request {
         tokio::task::spawn(async move {
@@ -115,14 +204,42 @@ async fn main() -> Result<()> {
         });
     }
 
+    if config.server_snapshot {
+        let app_data_c = app_data.clone();
+
+        tokio::task::spawn(async move {
+            // 1 hour interval
+            let mut interval = interval(Duration::from_secs(60 * 60));
+
+            loop {
+                interval.tick().await;
+
+                match create_scheduled_snapshot(&app_data_c).await {
+                    Ok(item) => {
+                        info!("scheduled snapshot created: {:?}", item);
+                    }
+                    Err(err) => {
+                        error!("scheduled snapshot error: {:?}", err);
+                    }
+                }
+            }
+        });
+    }
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::clone(&app_data))
+            .wrap(Authentication)
             .configure(user_config)
             .configure(profile_config)
             .configure(person_config)
             .configure(system_config)
             .configure(ping::ping_config)
+            .configure(api_key_config)
+            .configure(backup_config)
+            .configure(snapshot_config)
+            .configure(health_config)
+            .configure(migration_config)
     })
     .bind(format!("0.0.0.0:{}", config.port))?
     .run()
@@ -1,8 +1,25 @@
 // SPDX-License-Identifier: GPL-3.0-only
+use super::audit::store::AuditLog;
+use super::auth::api_key::ApiKeyStore;
+use super::auth::model::SessionSecret;
+use super::backup::store::BackupStore;
+use super::constant::{
+    DEFAULT_BACKUP_MAX_AGE_DAYS, DEFAULT_BACKUP_MAX_COUNT, DEFAULT_LOGIN_BACKOFF_BASE_SECS,
+    DEFAULT_LOGIN_LOCKOUT_SECS, DEFAULT_LOGIN_MAX_FAILURES, DEFAULT_SESSION_EXPIRE_SECS,
+    DEFAULT_SNAPSHOT_DAILY_COUNT, DEFAULT_SNAPSHOT_HOURLY_COUNT, DEFAULT_SNAPSHOT_WEEKLY_COUNT,
+};
+use super::health::store::QuarantineStore;
+use super::lock::ProfileLockRegistry;
 use super::person::model::Person;
 use super::profile::model::Profile;
+use super::snapshot::store::SnapshotStore;
 use super::system::model::RankItem;
+use super::user::model::RegisterChallenge;
+use super::user::store::UserStore;
+use super::user::throttle::LoginThrottle;
+use super::version_update::store::MigrationStore;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tokio::sync::Mutex;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -13,6 +30,71 @@ pub struct Config {
     pub server_upload_temp_folder_path: String,
     pub server_hourly_request: bool,
     pub port: u32,
+    // 会话签名密钥, 为空时启动时随机生成(重启后需重新登录)
+    #[serde(default)]
+    pub session_secret: String,
+    #[serde(default = "default_session_expire_secs")]
+    pub session_expire_secs: u64,
+    // 登录失败限流
+    #[serde(default = "default_login_max_failures")]
+    pub login_max_failures: u32,
+    #[serde(default = "default_login_backoff_base_secs")]
+    pub login_backoff_base_secs: u64,
+    #[serde(default = "default_login_lockout_secs")]
+    pub login_lockout_secs: u64,
+    // 存档备份保留数量与天数, 0 表示不限制
+    #[serde(default = "default_backup_max_count")]
+    pub backup_max_count: usize,
+    #[serde(default = "default_backup_max_age_days")]
+    pub backup_max_age_days: u64,
+    // 定时打包整个存档目录
+    #[serde(default)]
+    pub server_snapshot: bool,
+    #[serde(default = "default_snapshot_hourly_count")]
+    pub snapshot_hourly_count: usize,
+    #[serde(default = "default_snapshot_daily_count")]
+    pub snapshot_daily_count: usize,
+    #[serde(default = "default_snapshot_weekly_count")]
+    pub snapshot_weekly_count: usize,
+    // 启动时只生成存档升级报告, 不修改存档, 生成后退出
+    #[serde(default)]
+    pub migrate_dry_run: bool,
+}
+
+fn default_session_expire_secs() -> u64 {
+    DEFAULT_SESSION_EXPIRE_SECS
+}
+
+fn default_login_max_failures() -> u32 {
+    DEFAULT_LOGIN_MAX_FAILURES
+}
+
+fn default_login_backoff_base_secs() -> u64 {
+    DEFAULT_LOGIN_BACKOFF_BASE_SECS
+}
+
+fn default_login_lockout_secs() -> u64 {
+    DEFAULT_LOGIN_LOCKOUT_SECS
+}
+
+fn default_backup_max_count() -> usize {
+    DEFAULT_BACKUP_MAX_COUNT
+}
+
+fn default_backup_max_age_days() -> u64 {
+    DEFAULT_BACKUP_MAX_AGE_DAYS
+}
+
+fn default_snapshot_hourly_count() -> usize {
+    DEFAULT_SNAPSHOT_HOURLY_COUNT
+}
+
+fn default_snapshot_daily_count() -> usize {
+    DEFAULT_SNAPSHOT_DAILY_COUNT
+}
+
+fn default_snapshot_weekly_count() -> usize {
+    DEFAULT_SNAPSHOT_WEEKLY_COUNT
 }
 
 #[derive(Debug)]
@@ -21,7 +103,8 @@ pub struct AppData {
     pub server_data_folder_path: String,
     pub server_log_folder_path: String,
     pub server_upload_temp_folder_path: String,
-    pub user_json_lock: Mutex<u8>,
+    pub user_store: UserStore,
+    pub login_throttle: LoginThrottle,
     // query_all snapshot
     pub snapshot_data: Mutex<Vec<(u64, Person, Profile)>>,
     // cache string
@@ -30,6 +113,27 @@ pub struct AppData {
     pub snapshot_time: Mutex<String>,
     // cache ranks
     pub snapshot_ranks: Mutex<Vec<RankItem>>,
+    // session
+    pub session_secret: SessionSecret,
+    pub session_expire_secs: u64,
+    // 已注销的会话 id -> 过期时间戳
+    pub revoked_session_map: Mutex<HashMap<String, i64>>,
+    // 待确认的注册验证 challenge_id -> challenge
+    pub register_challenge_map: Mutex<HashMap<String, RegisterChallenge>>,
+    // 供机器人与外部工具使用的 API key
+    pub api_key_store: ApiKeyStore,
+    // 存档修改审计日志
+    pub audit_log: AuditLog,
+    // 存档读写锁
+    pub profile_lock_registry: ProfileLockRegistry,
+    // 存档修改前的自动备份
+    pub backup_store: BackupStore,
+    // 存档目录快照
+    pub snapshot_store: SnapshotStore,
+    // 隔离的损坏存档
+    pub quarantine_store: QuarantineStore,
+    // 存档升级前的原始文件
+    pub migration_store: MigrationStore,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -66,6 +170,38 @@ impl ResponseJson {
         }
     }
 
+    pub fn set_unauthorized_msg(&self, msg: &str) -> Self {
+        Self {
+            status: 401,
+            code: -1,
+            message: String::from(msg),
+        }
+    }
+
+    pub fn set_too_many_requests_msg(&self, msg: &str) -> Self {
+        Self {
+            status: 429,
+            code: -1,
+            message: String::from(msg),
+        }
+    }
+
+    pub fn set_forbidden_msg(&self, msg: &str) -> Self {
+        Self {
+            status: 403,
+            code: -1,
+            message: String::from(msg),
+        }
+    }
+
+    pub fn set_precondition_failed_msg(&self, msg: &str) -> Self {
+        Self {
+            status: 412,
+            code: -1,
+            message: String::from(msg),
+        }
+    }
+
     pub fn set_successful_msg(&self, msg: &str) -> Self {
         Self {
             status: 200,
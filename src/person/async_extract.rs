@@ -10,7 +10,7 @@ use anyhow::{anyhow, Result};
 use futures::{self, FutureExt, SinkExt};
 use rayon::prelude::*;
 use tokio;
-use tracing::{error, info};
+use tracing::{error, warn};
 
 type ExtractAllType = (u64, Person, Profile);
 type ExtractAllVec = Vec<ExtractAllType>;
@@ -81,40 +81,21 @@ pub async fn async_extract_query_data(folder_path: String) -> Result<ExtractAllV
     let person_vec = futures::future::try_join_all(person_future_vec).await?;
     let profile_vec = futures::future::try_join_all(profile_future_vec).await?;
 
-    let res_v: Result<ExtractAllVec> = entries
-        .into_par_iter()
-        .enumerate()
-        .map(|(index, id)| {
-            // person
-            let person = person_vec
-                .get(index)
-                .ok_or(anyhow!("error in profile_vec get: {}", index))?;
-
-            let person = person
-                .as_ref()
-                .map_err(|err| anyhow!("ID: {} err in person extract: {:?}", id, err))
-                .unwrap();
-
-            let person = person.clone();
-
-            // profile
-            let profile = profile_vec
-                .get(index)
-                .ok_or(anyhow!("error in profile_vec get: {}", index))?;
-
-            let profile = profile
-                .as_ref()
-                .map_err(|err| anyhow!("ID: {} err in profile extract: {:?}", id, err))
-                .unwrap();
-
-            let profile = profile.clone();
-
-            let push_item: ExtractAllType = (id, person, profile);
-            Ok(push_item)
+    // 单个存档损坏时跳过该存档, 不影响其他存档的快照
+    let res_v: ExtractAllVec = person_vec
+        .into_iter()
+        .zip(profile_vec)
+        .zip(entries)
+        .filter_map(|((person, profile), id)| match (person, profile) {
+            (Ok(person), Ok(profile)) => Some((id, person, profile)),
+            _ => {
+                warn!("skip {} in query data snapshot", id);
+                None
+            }
         })
         .collect();
 
-    Ok(res_v?)
+    Ok(res_v)
 }
 
 pub async fn async_extract_all_person_and_profiles(folder_path: String) -> Result<ExtractAllVec> {
@@ -189,8 +170,7 @@ pub async fn async_extract_all_person_and_profiles(folder_path: String) -> Resul
 
             let person = person
                 .as_ref()
-                .map_err(|err| anyhow!("ID: {} err in person extract: {:?}", id, err))
-                .unwrap();
+                .map_err(|err| anyhow!("ID: {} err in person extract: {:?}", id, err))?;
 
             let person = person.clone();
 
@@ -201,8 +181,7 @@ pub async fn async_extract_all_person_and_profiles(folder_path: String) -> Resul
 
             let profile = profile
                 .as_ref()
-                .map_err(|err| anyhow!("ID: {} err in profile extract: {:?}", id, err))
-                .unwrap();
+                .map_err(|err| anyhow!("ID: {} err in profile extract: {:?}", id, err))?;
 
             let profile = profile.clone();
 
@@ -217,24 +196,52 @@ pub async fn async_extract_all_person_and_profiles(folder_path: String) -> Resul
 type ExtractPersonType = (u64, Person);
 type ExtractPersonVec = Vec<ExtractPersonType>;
 
-pub async fn async_extract_all_person(folder_path: String) -> Result<ExtractPersonVec> {
-    let mut folder_entries = tokio::fs::read_dir(&folder_path).await?;
+// 按扩展名列出存档目录中的全部存档 id, 以及文件名无法解析为 id 的文件
+pub async fn async_scan_all_id(
+    folder_path: &str,
+    extension: &str,
+) -> Result<(Vec<u64>, Vec<String>)> {
+    let mut folder_entries = tokio::fs::read_dir(folder_path).await?;
 
     let mut entries: Vec<u64> = Vec::new();
+    let mut invalid_name_list: Vec<String> = Vec::new();
 
     while let Some(entry) = folder_entries.next_entry().await? {
-        let file_name = entry.file_name();
-        let file_name_str = String::from(file_name.to_str().unwrap());
+        let file_name_str = entry.file_name().to_string_lossy().to_string();
 
-        if file_name_str.ends_with(".person") {
-            let vec = file_name_str.split(".").collect::<Vec<_>>();
+        if let Some(stem) = file_name_str.strip_suffix(extension) {
+            match stem.parse::<u64>() {
+                Ok(id) => entries.push(id),
+                Err(_) => invalid_name_list.push(file_name_str),
+            }
+        }
+    }
 
-            let id: u64 = vec.first().unwrap().parse().unwrap();
+    Ok((entries, invalid_name_list))
+}
 
-            entries.push(id);
-        }
+// 文件名无法解析为 id 的文件直接跳过
+async fn async_get_all_id(folder_path: &str, extension: &str) -> Result<Vec<u64>> {
+    let (entries, invalid_name_list) = async_scan_all_id(folder_path, extension).await?;
+
+    if !invalid_name_list.is_empty() {
+        warn!("skip invalid file name: {:?}", invalid_name_list);
     }
 
+    Ok(entries)
+}
+
+pub async fn async_get_all_person_id(folder_path: &str) -> Result<Vec<u64>> {
+    async_get_all_id(folder_path, ".person").await
+}
+
+pub async fn async_get_all_profile_id(folder_path: &str) -> Result<Vec<u64>> {
+    async_get_all_id(folder_path, ".profile").await
+}
+
+pub async fn async_extract_all_person(folder_path: String) -> Result<ExtractPersonVec> {
+    let entries = async_get_all_person_id(&folder_path).await?;
+
     let person_future_vec = entries
         .clone()
         .into_iter()
@@ -242,15 +249,15 @@ pub async fn async_extract_all_person(folder_path: String) -> Result<ExtractPers
             let cloned_folder_path = folder_path.clone();
 
             return tokio::spawn(async move {
-                let person = extract_person(id, &cloned_folder_path).unwrap();
-                (id, person)
+                let person = extract_person(id, &cloned_folder_path)?;
+                Ok((id, person))
             });
         })
         .collect::<Vec<_>>();
 
     let person_vec = futures::future::try_join_all(person_future_vec).await?;
 
-    Ok(person_vec)
+    person_vec.into_iter().collect()
 }
 
 pub async fn async_extract_selected_person(
@@ -263,13 +270,13 @@ pub async fn async_extract_selected_person(
             let cloned_folder_path = folder_path.clone();
 
             return tokio::spawn(async move {
-                let person = extract_person(id, &cloned_folder_path).unwrap();
-                (id, person)
+                let person = extract_person(id, &cloned_folder_path)?;
+                Ok((id, person))
             });
         })
         .collect::<Vec<_>>();
 
     let person_vec = futures::future::try_join_all(person_future_vec).await?;
 
-    Ok(person_vec)
+    person_vec.into_iter().collect()
 }
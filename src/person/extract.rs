@@ -1,250 +1,255 @@
 // SPDX-License-Identifier: GPL-3.0-only
 use crate::{
-    constant::{MAX_DEFAULT_STASH_LEN, MAX_PERSON_FILE_VERSION},
-    person::model::{ItemTag, OrderTag, Person, StashItemTag},
+    constant::MAX_PERSON_FILE_VERSION,
+    person::model::{ItemTag, OrderTag, Person},
     profile::{extract::extract_profile, model::Profile},
+    version_update::update_to_154::extract_person_v153,
+    xml::{
+        error::ParseError,
+        extract::extract_element_tree,
+        model::{XmlElement, XmlExtra, XmlNode},
+    },
 };
 use anyhow::{anyhow, Result};
-use quick_xml::{events::Event, Reader};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use std::{collections::HashMap, fs, io, str};
-use tracing::info;
+use std::{fs, io};
 
 use super::model::ItemGroupTag;
 
-pub fn extract_person(id: u64, folder_path: &str) -> Result<Person> {
-    // 优先以最高版本解析
-    let mut person = Person::default();
+pub fn extract_person(id: u64, folder_path: &str) -> Result<Person, ParseError> {
+    let content = read_person_file(id, folder_path)?;
+
+    extract_person_any_version(&content)
+}
 
+fn read_person_file(id: u64, folder_path: &str) -> Result<String, ParseError> {
     let path = format!("{}/{}.person", folder_path, id);
 
-    let mut reader = Reader::from_file(path)?;
-
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-
-    let mut is_in_person = false;
-    let mut is_in_stash = false;
-    let mut is_in_backpack = false;
-
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(e)) => {
-                // println!("start e.name: {:?}", str::from_utf8(e.name()).unwrap());
-                match e.name() {
-                    b"person" => {
-                        is_in_person = true;
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            match attr_key {
-                                b"max_authority_reached" => {
-                                    person.max_authority_reached = attr_value.parse()?;
-                                }
-                                b"authority" => {
-                                    person.authority = attr_value.parse()?;
-                                }
-                                b"job_points" => {
-                                    person.job_points = attr_value.parse()?;
-                                }
-                                b"faction" => {
-                                    person.faction = attr_value;
-                                }
-                                b"name" => {
-                                    person.name = attr_value;
-                                }
-                                b"version" => {
-                                    person.version = attr_value.parse()?;
-                                }
-                                b"alive" => {
-                                    person.alive = attr_value.parse()?;
-                                }
-                                b"soldier_group_id" => {
-                                    person.soldier_group_id = attr_value.parse()?;
-                                }
-                                b"soldier_group_name" => {
-                                    person.soldier_group_name = attr_value;
-                                }
-                                b"block" => {
-                                    person.block = attr_value;
-                                }
-                                b"squad_size_setting" => {
-                                    person.squad_size_setting = attr_value.parse()?;
-                                }
-                                b"order" => {
-                                    let mut order_item = OrderTag::default();
-                                }
-                                _ => (),
-                            }
-                        }
-                        // println!("This is person");
-                    }
-                    b"stash" => {
-                        is_in_stash = true;
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            match attr_key {
-                                b"hard_capacity" => {
-                                    person.stash_hard_capacity = attr_value.parse()?;
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    b"backpack" => {
-                        is_in_backpack = true;
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            match attr_key {
-                                b"hard_capacity" => {
-                                    person.backpack_hard_capacity = attr_value.parse()?;
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    _ => (),
-                }
+    fs::read_to_string(&path).map_err(|err| ParseError::from_io(&path, err))
+}
+
+fn extract_person_root(content: &str) -> Result<XmlElement, ParseError> {
+    let root = extract_element_tree(content)?;
+
+    if root.name != "person" {
+        return Err(ParseError::MalformedXml {
+            position: 0,
+            message: format!("root element is not person: {}", root.name),
+        });
+    }
+
+    Ok(root)
+}
+
+// 缺少 version 属性时按当前版本处理
+fn get_person_version(root: &XmlElement) -> Result<u16, ParseError> {
+    root.attr_list
+        .iter()
+        .find(|attr| attr.key == "version")
+        .map(|attr| attr.parse())
+        .unwrap_or(Ok(MAX_PERSON_FILE_VERSION))
+}
+
+// 先读取 version, 再按版本选择解析方式, 旧版本存档转换为当前版本
+// 不支持的版本直接返回错误, 避免按当前版本解析后写坏存档
+pub fn extract_person_any_version(content: &str) -> Result<Person, ParseError> {
+    let root = extract_person_root(content)?;
+
+    match get_person_version(&root)? {
+        version if version == MAX_PERSON_FILE_VERSION => extract_person_from_element(root),
+        153 => Ok(extract_person_v153(root)?.into()),
+        version => Err(ParseError::UnsupportedVersion {
+            expected: MAX_PERSON_FILE_VERSION.into(),
+            got: version.into(),
+        }),
+    }
+}
+
+// 按当前版本解析, 不检查 version
+// 未识别的属性与子节点保存在 extra 中, 保存时原样写回
+fn extract_person_from_element(root: XmlElement) -> Result<Person, ParseError> {
+    let mut person = Person::default();
+
+    for attr in root.attr_list {
+        match attr.key.as_str() {
+            "max_authority_reached" => {
+                person.max_authority_reached = attr.parse()?;
+            }
+            "authority" => {
+                person.authority = attr.parse()?;
+            }
+            "job_points" => {
+                person.job_points = attr.parse()?;
             }
-            Ok(Event::Empty(e)) => {
-                // println!("empty e.name: {:?}", str::from_utf8(e.name())?);
-
-                match e.name() {
-                    b"order" => {
-                        let mut order_item = OrderTag::default();
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            // println!("attr: {}, value: {}", str::from_utf8(attr_key)?, attr_value);
-
-                            match attr_key {
-                                b"moving" => {
-                                    order_item.moving = attr_value.parse()?;
-                                }
-                                b"target" => {
-                                    order_item.target = attr_value;
-                                }
-                                b"class" => {
-                                    order_item.class = attr_value.parse()?;
-                                }
-                                _ => (),
-                            }
-                        }
-
-                        person.order = order_item;
-                    }
-                    // item_group 仅在 1.94 中存在, 且仅在 stash/backpack 中
-                    b"item_group" => {
-                        let mut item_group = ItemGroupTag::default();
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            // println!(
-                            //     "attr: {}, value: {}",
-                            //     str::from_utf8(attr_key)?,
-                            //     attr_value
-                            // );
-
-                            match attr_key {
-                                b"class" => {
-                                    item_group.class = attr_value.parse()?;
-                                }
-                                b"index" => {
-                                    item_group.index = attr_value.parse()?;
-                                }
-                                b"key" => {
-                                    item_group.key = attr_value.parse()?;
-                                }
-                                b"amount" => {
-                                    item_group.amount = attr_value.parse()?;
-                                }
-                                _ => (),
-                            }
-                        }
-
-                        if is_in_backpack {
-                            person.backpack_item_list.push(item_group);
-                        } else if is_in_stash {
-                            person.stash_item_list.push(item_group);
-                        }
-                    }
-                    b"item" => {
-                        let mut item_tag = ItemTag::default();
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            // println!(
-                            //     "attr: {}, value: {}",
-                            //     str::from_utf8(attr_key)?,
-                            //     attr_value
-                            // );
-
-                            match attr_key {
-                                b"slot" => {
-                                    item_tag.slot = attr_value.parse()?;
-                                }
-                                b"index" => {
-                                    item_tag.index = attr_value.parse()?;
-                                }
-                                b"amount" => {
-                                    item_tag.amount = attr_value.parse()?;
-                                }
-                                b"key" => {
-                                    item_tag.key = attr_value;
-                                }
-                                _ => (),
-                            }
-                        }
-
-                        person.item_list.push(item_tag);
-                    }
-                    b"backpack" => {}
-                    _ => (),
-                }
+            "faction" => {
+                person.faction = attr.value;
             }
-            Ok(Event::End(e)) => {
-                // DEBUG
-                // println!("end e.name: {:?}", str::from_utf8(e.name())?);
+            "name" => {
+                person.name = attr.value;
             }
-            Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {} : {:?}", reader.buffer_position(), e),
-            _ => (),
+            "version" => {
+                person.version = attr.parse()?;
+            }
+            "alive" => {
+                person.alive = attr.parse()?;
+            }
+            "soldier_group_id" => {
+                person.soldier_group_id = attr.parse()?;
+            }
+            "soldier_group_name" => {
+                person.soldier_group_name = attr.value;
+            }
+            "block" => {
+                person.block = attr.value;
+            }
+            "squad_size_setting" => {
+                person.squad_size_setting = attr.parse()?;
+            }
+            _ => person.extra.attr_list.push(attr),
         }
     }
 
-    if person.version != MAX_PERSON_FILE_VERSION {
-        anyhow!(
-            "{} Person file \"version\" not correct, expected: {}, got: {}",
-            id,
-            MAX_PERSON_FILE_VERSION,
-            person.version
-        );
+    for (index, node) in root.child_list.into_iter().enumerate() {
+        match node {
+            XmlNode::Element(element) if element.name == "order" => {
+                person.order = extract_order(element)?;
+            }
+            XmlNode::Element(element) if element.name == "item" => {
+                person.item_list.push(extract_item(element)?);
+            }
+            XmlNode::Element(element) if element.name == "stash" => {
+                person.stash_extra = extract_item_group_container(
+                    element,
+                    &mut person.stash_hard_capacity,
+                    &mut person.stash_item_list,
+                )?;
+            }
+            XmlNode::Element(element) if element.name == "backpack" => {
+                person.backpack_extra = extract_item_group_container(
+                    element,
+                    &mut person.backpack_hard_capacity,
+                    &mut person.backpack_item_list,
+                )?;
+            }
+            node => person.extra.push_child(index, node),
+        }
     }
 
     Ok(person)
 }
 
+pub fn extract_order(element: XmlElement) -> Result<OrderTag, ParseError> {
+    let mut order_item = OrderTag::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "moving" => {
+                order_item.moving = attr.parse()?;
+            }
+            "target" => {
+                order_item.target = attr.value;
+            }
+            "class" => {
+                order_item.class = attr.parse()?;
+            }
+            _ => order_item.extra.attr_list.push(attr),
+        }
+    }
+
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        order_item.extra.push_child(index, node);
+    }
+
+    Ok(order_item)
+}
+
+pub fn extract_item(element: XmlElement) -> Result<ItemTag, ParseError> {
+    let mut item_tag = ItemTag::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "slot" => {
+                item_tag.slot = attr.parse()?;
+            }
+            "index" => {
+                item_tag.index = attr.parse()?;
+            }
+            "amount" => {
+                item_tag.amount = attr.parse()?;
+            }
+            "key" => {
+                item_tag.key = attr.value;
+            }
+            _ => item_tag.extra.attr_list.push(attr),
+        }
+    }
+
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        item_tag.extra.push_child(index, node);
+    }
+
+    Ok(item_tag)
+}
+
+// item_group 仅在 1.94 中存在, 且仅在 stash/backpack 中
+fn extract_item_group(element: XmlElement) -> Result<ItemGroupTag, ParseError> {
+    let mut item_group = ItemGroupTag::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "class" => {
+                item_group.class = attr.parse()?;
+            }
+            "index" => {
+                item_group.index = attr.parse()?;
+            }
+            "key" => {
+                item_group.key = attr.value;
+            }
+            "amount" => {
+                item_group.amount = attr.parse()?;
+            }
+            _ => item_group.extra.attr_list.push(attr),
+        }
+    }
+
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        item_group.extra.push_child(index, node);
+    }
+
+    Ok(item_group)
+}
+
+// stash / backpack, 返回未识别的属性与子节点
+fn extract_item_group_container(
+    element: XmlElement,
+    hard_capacity: &mut u16,
+    item_list: &mut Vec<ItemGroupTag>,
+) -> Result<XmlExtra, ParseError> {
+    let mut extra = XmlExtra::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            // 1.92 新增: 容量上限
+            "hard_capacity" => {
+                *hard_capacity = attr.parse()?;
+            }
+            _ => extra.attr_list.push(attr),
+        }
+    }
+
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        match node {
+            XmlNode::Element(element) if element.name == "item_group" => {
+                item_list.push(extract_item_group(element)?);
+            }
+            node => extra.push_child(index, node),
+        }
+    }
+
+    Ok(extra)
+}
+
 pub fn extract_all_person(folder_path: &str) -> Result<Vec<(u64, Person)>> {
     let entries = fs::read_dir(folder_path)?
         .map(|res| res.map(|e| e.path()))
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     constant::{MAX_DEFAULT_BACKPACK_LEN, MAX_DEFAULT_STASH_LEN, MAX_PERSON_FILE_VERSION},
     profile::model::Profile,
+    xml::model::XmlExtra,
 };
 
 // 1.94 调整: StashItemTag -> ItemGroupTag
@@ -20,6 +21,8 @@ pub struct ItemTag {
     pub index: i32,
     pub amount: i8,
     pub key: String,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
 }
 
 // 1.94 调整: StashItemTag -> ItemGroupTga
@@ -29,6 +32,8 @@ pub struct ItemGroupTag {
     pub class: i8,
     pub amount: i32,
     pub key: String,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,6 +41,8 @@ pub struct OrderTag {
     pub moving: i32,
     pub target: String,
     pub class: i8,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -63,6 +70,13 @@ pub struct Person {
     pub backpack_item_list: Vec<ItemGroupTag>,
     // 1.94 调整: StashItemTag -> ItemTag
     pub stash_item_list: Vec<ItemGroupTag>,
+    // 未识别的 person / stash / backpack 属性与子节点
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub stash_extra: XmlExtra,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub backpack_extra: XmlExtra,
 }
 
 impl Default for Person {
@@ -85,6 +99,9 @@ impl Default for Person {
             stash_hard_capacity: MAX_DEFAULT_STASH_LEN,
             backpack_item_list: vec![],
             stash_item_list: vec![],
+            extra: XmlExtra::default(),
+            stash_extra: XmlExtra::default(),
+            backpack_extra: XmlExtra::default(),
         }
     }
 }
@@ -107,12 +124,16 @@ impl From<&Person> for Person {
                 moving: p.order.moving,
                 class: p.order.class,
                 target: p.order.target.to_owned(),
+                extra: p.order.extra.clone(),
             },
             item_list: p.item_list.to_vec(),
             backpack_hard_capacity: p.backpack_hard_capacity,
             stash_hard_capacity: p.stash_hard_capacity,
             backpack_item_list: p.backpack_item_list.to_vec(),
             stash_item_list: p.stash_item_list.to_vec(),
+            extra: p.extra.clone(),
+            stash_extra: p.stash_extra.clone(),
+            backpack_extra: p.backpack_extra.clone(),
         }
     }
 }
@@ -123,6 +144,7 @@ impl Default for OrderTag {
             moving: 0,
             target: String::new(),
             class: 0,
+            extra: XmlExtra::default(),
         }
     }
 }
@@ -134,6 +156,7 @@ impl Default for ItemTag {
             index: 0,
             amount: 0,
             key: String::new(),
+            extra: XmlExtra::default(),
         }
     }
 }
@@ -155,6 +178,7 @@ impl Default for ItemGroupTag {
             index: -1,
             key: String::new(),
             amount: 1,
+            extra: XmlExtra::default(),
         }
     }
 }
@@ -180,6 +204,28 @@ pub struct InsertSelectedPersonBackpackReq {
     pub backpack_item_list: Vec<ItemGroupTag>,
 }
 
+// 为 true 时合并 class / index / key 相同的物品组
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MergeItemQuery {
+    #[serde(default)]
+    pub merge: bool,
+}
+
+// 批量插入物品默认与已有的物品组合并, 为 true 时追加到末尾
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InsertItemQuery {
+    #[serde(default)]
+    pub append: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompactInventoryRes {
+    pub backpack_before: usize,
+    pub backpack_after: usize,
+    pub stash_before: usize,
+    pub stash_after: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UpdateAllPersonSoldierGroupReq {
     pub group: String,
@@ -1,12 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-only
 use super::model::{ItemGroupTag, ItemTag, Person};
-use crate::constant::MAX_DEFAULT_BACKPACK_LEN;
+use crate::backup::{model::BackupKind, store::BackupStore};
 use crate::person::{extract::extract_person, model::StashItemTag};
+use crate::utils::write_file_atomic;
+use crate::xml::save::{push_extra_attr_list, write_leaf_element, ExtraChildWriter};
 use anyhow::Result;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
 use std::collections::{HashMap, HashSet};
-use std::io::{Cursor, Write};
+use std::io::Cursor;
 use tracing::{error, warn};
 
 pub fn save_person(p: &Person) -> Result<String> {
@@ -31,16 +33,21 @@ pub fn save_person(p: &Person) -> Result<String> {
         "squad_size_setting",
         p.squad_size_setting.to_string().as_str(),
     ));
+    push_extra_attr_list(&mut person_tag, &p.extra);
 
     writer.write_event(Event::Start(person_tag))?;
 
+    // 未识别的子节点按原始位置穿插写回
+    let mut person_child_writer = ExtraChildWriter::new(&p.extra);
+
     let mut order_tag = BytesStart::owned(b"order".to_owned(), "order".len());
 
     order_tag.push_attribute(("moving", p.order.moving.to_string().as_str()));
     order_tag.push_attribute(("target", p.order.target.to_string().as_str()));
     order_tag.push_attribute(("class", p.order.class.to_string().as_str()));
 
-    writer.write_event(Event::Empty(order_tag))?;
+    person_child_writer.write_before_known(&mut writer)?;
+    write_leaf_element(&mut writer, order_tag, &p.order.extra)?;
 
     for item in p.item_list.iter() {
         let mut item_tag = BytesStart::owned(b"item".to_owned(), "item".len());
@@ -50,16 +57,21 @@ pub fn save_person(p: &Person) -> Result<String> {
         item_tag.push_attribute(("amount", item.amount.to_string().as_str()));
         item_tag.push_attribute(("key", item.key.as_str()));
 
-        writer.write_event(Event::Empty(item_tag))?;
+        person_child_writer.write_before_known(&mut writer)?;
+        write_leaf_element(&mut writer, item_tag, &item.extra)?;
     }
 
     let mut stash_tag = BytesStart::owned(b"stash".to_owned(), "stash".len());
 
     // 1.92 新增: 仓库上限
     stash_tag.push_attribute(("hard_capacity", p.stash_hard_capacity.to_string().as_str()));
+    push_extra_attr_list(&mut stash_tag, &p.stash_extra);
 
+    person_child_writer.write_before_known(&mut writer)?;
     writer.write_event(Event::Start(stash_tag))?;
 
+    let mut stash_child_writer = ExtraChildWriter::new(&p.stash_extra);
+
     // 1.94: v154
     for item in p.stash_item_list.iter() {
         let mut stash_item_tag = BytesStart::owned(b"item_group".to_owned(), "item_group".len());
@@ -69,9 +81,12 @@ pub fn save_person(p: &Person) -> Result<String> {
         stash_item_tag.push_attribute(("key", item.key.as_str()));
         stash_item_tag.push_attribute(("amount", item.amount.to_string().as_str()));
 
-        writer.write_event(Event::Empty(stash_item_tag))?;
+        stash_child_writer.write_before_known(&mut writer)?;
+        write_leaf_element(&mut writer, stash_item_tag, &item.extra)?;
     }
 
+    stash_child_writer.write_rest(&mut writer)?;
+
     writer.write_event(Event::End(BytesEnd::borrowed(b"stash")))?;
 
     let mut backpack_tag = BytesStart::owned(b"backpack".to_owned(), "backpack".len());
@@ -81,12 +96,17 @@ pub fn save_person(p: &Person) -> Result<String> {
         "hard_capacity",
         p.backpack_hard_capacity.to_string().as_str(),
     ));
+    push_extra_attr_list(&mut backpack_tag, &p.backpack_extra);
 
-    if p.backpack_item_list.len() == 0 {
+    person_child_writer.write_before_known(&mut writer)?;
+
+    if p.backpack_item_list.is_empty() && p.backpack_extra.child_list.is_empty() {
         writer.write_event(Event::Empty(backpack_tag))?;
     } else {
         writer.write_event(Event::Start(backpack_tag))?;
 
+        let mut backpack_child_writer = ExtraChildWriter::new(&p.backpack_extra);
+
         // 1.94: v154
         for item in p.backpack_item_list.iter() {
             let mut backpack_item_tag =
@@ -97,12 +117,17 @@ pub fn save_person(p: &Person) -> Result<String> {
             backpack_item_tag.push_attribute(("key", item.key.as_str()));
             backpack_item_tag.push_attribute(("amount", item.amount.to_string().as_str()));
 
-            writer.write_event(Event::Empty(backpack_item_tag))?;
+            backpack_child_writer.write_before_known(&mut writer)?;
+            write_leaf_element(&mut writer, backpack_item_tag, &item.extra)?;
         }
 
+        backpack_child_writer.write_rest(&mut writer)?;
+
         writer.write_event(Event::End(BytesEnd::borrowed(b"backpack")))?;
     }
 
+    person_child_writer.write_rest(&mut writer)?;
+
     writer.write_event(Event::End(BytesEnd::borrowed(b"person")))?;
 
     let result = String::from_utf8(writer.into_inner().into_inner())?;
@@ -115,93 +140,141 @@ pub fn save_person_to_file(path: &str, id: u64, person: &Person) -> Result<()> {
 
     let target_path = format!("{}/{}.person", path, id);
 
-    let mut file = std::fs::File::create(target_path)?;
+    write_file_atomic(target_path, res_person_str.as_bytes())
+}
 
-    file.write_all(res_person_str.as_bytes())?;
+// 保存前先备份当前存档
+pub fn save_person_to_file_with_backup(
+    path: &str,
+    id: u64,
+    person: &Person,
+    backup_store: &BackupStore,
+) -> Result<()> {
+    backup_store.backup(path, id, BackupKind::Person)?;
 
-    Ok(())
+    save_person_to_file(path, id, person)
 }
 
-pub async fn insert_person_list_backpack_to_file(
+// 批量保存, 调用方只传入实际修改的存档, 避免无意义的备份挤占保留份数
+async fn save_person_list_to_file_with_backup(
     path: &str,
-    all_person_list: &Vec<(u64, Person)>,
-    item_list: &Vec<ItemGroupTag>,
+    person_list: &[(u64, Person)],
+    backup_store: &BackupStore,
 ) -> Result<()> {
-    let new_all_person_list: Vec<(u64, Person)> = all_person_list
-        .into_iter()
-        .map(|info| {
-            let (_id, _person) = info;
-            let id: u64 = _id.clone();
-            let mut new_person: Person = _person.clone();
-
-            let source_total_item_count = _person
-                .backpack_item_list
-                .iter()
-                .fold(0, |acc, item| acc + item.amount);
+    let future_vec = person_list.iter().cloned().map(|(id, person)| {
+        let cloned_folder_path = path.to_string();
+        let cloned_backup_store = backup_store.clone();
 
-            let insert_item_count = item_list.iter().fold(0, |acc, item| acc + item.amount);
+        tokio::spawn(async move {
+            save_person_to_file_with_backup(&cloned_folder_path, id, &person, &cloned_backup_store)
+        })
+    });
 
-            // 若超出, 终止操作
-            if source_total_item_count + insert_item_count
-                > new_person.backpack_hard_capacity.into()
-            {
-                error!("person id: {} backpack over 255", id);
-                return (id, new_person);
-            }
+    for res in futures::future::try_join_all(future_vec).await? {
+        res?;
+    }
 
-            let mut new_item_map: HashMap<String, ItemGroupTag> =
-                HashMap::with_capacity(MAX_DEFAULT_BACKPACK_LEN.into());
+    Ok(())
+}
 
-            for send_item in item_list {
-                new_item_map.insert(send_item.key.clone(), send_item.clone());
+// 合并 class / index / key 相同的物品组, 数量相加, 保持首次出现的顺序
+// 未识别的属性与子节点(extra)不同的物品组不合并, 避免丢失
+pub fn merge_item_group_list(item_list: Vec<ItemGroupTag>) -> Vec<ItemGroupTag> {
+    let mut index_map: HashMap<(i8, i32, String), Vec<usize>> = HashMap::new();
+    let mut new_item_list: Vec<ItemGroupTag> = Vec::with_capacity(item_list.len());
+
+    for item in item_list {
+        let index_list = index_map
+            .entry((item.class, item.index, item.key.clone()))
+            .or_default();
+
+        match index_list
+            .iter()
+            .find(|&&index| new_item_list[index].extra == item.extra)
+        {
+            Some(&index) => {
+                let target = &mut new_item_list[index];
+                target.amount = target.amount.saturating_add(item.amount);
             }
-
-            for source_item in _person.backpack_item_list.iter() {
-                new_item_map
-                    .entry(source_item.key.clone())
-                    .and_modify(|item| item.amount += source_item.amount)
-                    .or_insert(source_item.clone());
+            None => {
+                index_list.push(new_item_list.len());
+                new_item_list.push(item);
             }
+        }
+    }
 
-            let new_item_list = new_item_map
-                .values()
-                .map(|item| item.clone())
-                .collect::<Vec<ItemGroupTag>>();
+    new_item_list
+}
 
-            new_person.backpack_item_list = new_item_list;
+// 向背包插入物品, 默认与已有的物品组合并, append 为 true 时追加到末尾
+// 超出背包容量时返回 None
+pub fn insert_backpack_item_list(
+    id: u64,
+    person: &Person,
+    item_list: &[ItemGroupTag],
+    append: bool,
+) -> Option<Person> {
+    let mut new_person: Person = person.clone();
+
+    let source_total_item_count = person
+        .backpack_item_list
+        .iter()
+        .fold(0i32, |acc, item| acc.saturating_add(item.amount));
+
+    let insert_item_count = item_list
+        .iter()
+        .fold(0i32, |acc, item| acc.saturating_add(item.amount));
+
+    // 若超出, 终止操作
+    if source_total_item_count.saturating_add(insert_item_count)
+        > new_person.backpack_hard_capacity.into()
+    {
+        error!("person id: {} backpack over 255", id);
+        return None;
+    }
 
-            (id, new_person)
-        })
-        .collect();
+    new_person
+        .backpack_item_list
+        .extend(item_list.iter().cloned());
 
-    // for data in new_all_person_list.into_iter() {
-    //     save_person_to_file(path, data.0, &data.1)?;
-    // }
+    if !append {
+        new_person.backpack_item_list = merge_item_group_list(new_person.backpack_item_list);
+    }
 
-    let folder_path = path.to_string();
+    Some(new_person)
+}
 
-    let future_vec = new_all_person_list.into_iter().map(|info| {
-        let cloned_folder_path = folder_path.clone();
-        return tokio::spawn(
-            async move { save_person_to_file(&cloned_folder_path, info.0, &info.1) },
-        );
-    });
+pub async fn insert_person_list_backpack_to_file(
+    path: &str,
+    all_person_list: &Vec<(u64, Person)>,
+    item_list: &Vec<ItemGroupTag>,
+    append: bool,
+    backup_store: &BackupStore,
+) -> Result<Vec<(u64, Person)>> {
+    let new_all_person_list: Vec<(u64, Person)> = all_person_list
+        .iter()
+        .filter_map(|(id, person)| {
+            insert_backpack_item_list(*id, person, item_list, append)
+                .map(|new_person| (*id, new_person))
+        })
+        .collect();
 
-    futures::future::try_join_all(future_vec).await?;
+    save_person_list_to_file_with_backup(path, &new_all_person_list, backup_store).await?;
 
-    Ok(())
+    Ok(new_all_person_list)
 }
 
 pub async fn delete_person_item_list_to_file(
     path: &str,
     all_person_list: &Vec<(u64, Person)>,
     item_list: &Vec<String>,
-) -> Result<()> {
+    backup_store: &BackupStore,
+) -> Result<Vec<(u64, Person)>> {
     let key_set: HashSet<String> = item_list.iter().map(|item| item.clone()).collect();
 
     let new_all_person_list: Vec<(u64, Person)> = all_person_list
         .into_iter()
-        .map(|info| {
+        .filter_map(|info| {
             let (_id, _person) = info;
             let id: u64 = _id.clone();
             let mut new_person: Person = _person.clone();
@@ -236,22 +309,22 @@ pub async fn delete_person_item_list_to_file(
                 })
                 .collect();
 
-            (id, new_person)
+            // 没有匹配的物品时不保存
+            let is_changed = new_person.backpack_item_list.len()
+                != _person.backpack_item_list.len()
+                || new_person.stash_item_list.len() != _person.stash_item_list.len()
+                || _person
+                    .item_list
+                    .iter()
+                    .any(|item| key_set.contains(&item.key));
+
+            is_changed.then_some((id, new_person))
         })
         .collect();
 
-    let folder_path = path.to_string();
+    save_person_list_to_file_with_backup(path, &new_all_person_list, backup_store).await?;
 
-    let future_vec = new_all_person_list.into_iter().map(|info| {
-        let cloned_folder_path = folder_path.clone();
-        return tokio::spawn(
-            async move { save_person_to_file(&cloned_folder_path, info.0, &info.1) },
-        );
-    });
-
-    futures::future::try_join_all(future_vec).await?;
-
-    Ok(())
+    Ok(new_all_person_list)
 }
 
 pub async fn update_person_list_soldider_group_to_file(
@@ -259,12 +332,13 @@ pub async fn update_person_list_soldider_group_to_file(
     all_person_list: &Vec<(u64, Person)>,
     group: &str,
     cost: f32,
-) -> Result<Vec<u64>> {
+    backup_store: &BackupStore,
+) -> Result<(Vec<u64>, Vec<(u64, Person)>)> {
     let mut err_profile_id_vec = vec![];
 
     let new_all_person_list: Vec<(u64, Person)> = all_person_list
         .into_iter()
-        .map(|info| {
+        .filter_map(|info| {
             let (_id, _person) = info;
             let id: u64 = _id.clone();
             let mut new_person: Person = _person.clone();
@@ -273,26 +347,250 @@ pub async fn update_person_list_soldider_group_to_file(
             if new_person.job_points < cost {
                 error!("person id: {} rp < cost: {}", id, cost);
                 err_profile_id_vec.push(id);
-                return (id, new_person);
+                return None;
             }
 
             new_person.job_points = new_person.job_points - cost;
             new_person.soldier_group_name = group.to_string();
 
-            (id, new_person)
+            Some((id, new_person))
         })
         .collect();
 
-    let folder_path = path.to_string();
+    save_person_list_to_file_with_backup(path, &new_all_person_list, backup_store).await?;
+
+    Ok((err_profile_id_vec, new_all_person_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_backpack_item_list, merge_item_group_list, save_person};
+    use crate::person::{
+        extract::{extract_person, extract_person_any_version},
+        model::ItemGroupTag,
+    };
+    use crate::xml::{
+        error::ParseError,
+        extract::{extract_element_tree, read_fixture_list},
+        model::{XmlAttr, XmlExtra},
+    };
+
+    #[test]
+    fn round_trip_fixture_list() {
+        let fixture_list = read_fixture_list("person");
+        assert!(!fixture_list.is_empty());
+
+        for (name, content) in fixture_list {
+            let person = extract_person_any_version(&content).unwrap();
+            let saved = save_person(&person).unwrap();
+
+            let mut source_tree = extract_element_tree(&content).unwrap();
+            let mut saved_tree = extract_element_tree(&saved).unwrap();
+            source_tree.normalize();
+            saved_tree.normalize();
+
+            assert_eq!(source_tree, saved_tree, "fixture: {}", name);
+
+            // 保存结果再次读写后逐字节一致
+            let resaved = save_person(&extract_person_any_version(&saved).unwrap()).unwrap();
+
+            assert_eq!(saved, resaved, "fixture: {}", name);
+        }
+    }
+
+    #[test]
+    fn edit_keeps_unknown_xml() {
+        let (_, content) = read_fixture_list("person")
+            .into_iter()
+            .find(|(name, _)| name == "mod_extra.person")
+            .unwrap();
+
+        let mut person = extract_person_any_version(&content).unwrap();
+
+        person.job_points = 0.0;
+        person.item_list.clear();
+        person.backpack_item_list.push(ItemGroupTag {
+            key: "m16a4.weapon".to_string(),
+            ..ItemGroupTag::default()
+        });
+
+        let saved = save_person(&person).unwrap();
+
+        for expected in [
+            r#"custom_rank="veteran""#,
+            r#"<achievement key="first_blood""#,
+            r#"formation="wedge""#,
+            r#"<perk name="sprinter""#,
+            r#"<note>keep &amp; store</note>"#,
+            r#"<![CDATA[raw <data> kept]]>"#,
+            r#"<weapon key="g36.weapon" kills="90"/>"#,
+            r#"key="m16a4.weapon""#,
+        ] {
+            assert!(saved.contains(expected), "missing: {}", expected);
+        }
+
+        assert!(!saved.contains("<attachment"));
+    }
+
+    #[test]
+    fn parse_error_kind() {
+        let (_, content) = read_fixture_list("person")
+            .into_iter()
+            .find(|(name, _)| name == "basic.person")
+            .unwrap();
+
+        let err =
+            extract_person_any_version(&content.replace("</person>", "</persn>")).unwrap_err();
+        assert!(matches!(err, ParseError::MalformedXml { .. }), "{:?}", err);
+        assert_eq!(err.code(), 1003);
+
+        // 属性值错误时带上该元素在文件中的位置
+        let bad_content = content.replace(r#"amount="1""#, r#"amount="x""#);
+        let err = extract_person_any_version(&bad_content).unwrap_err();
+        let position = bad_content.find("<item ").unwrap();
+        match &err {
+            ParseError::InvalidAttr {
+                position: err_position,
+                key,
+                value,
+            } => {
+                // 位置为元素起始处 (可能包含其前的空白)
+                assert!(bad_content[*err_position..position].trim().is_empty());
+                assert_eq!((key.as_str(), value.as_str()), ("amount", "x"));
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+        assert_eq!(err.code(), 1004);
+
+        let err = extract_person(0, "tests/fixtures/missing").unwrap_err();
+        assert!(matches!(err, ParseError::NotFound { .. }), "{:?}", err);
+        assert_eq!(err.code(), 1001);
+
+        let folder = std::env::temp_dir().join(format!("rwr_parse_error_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(
+            folder.join("1.person"),
+            content.replace(r#"version="154""#, r#"version="152""#),
+        )
+        .unwrap();
+        let err = extract_person(1, folder.to_str().unwrap()).unwrap_err();
+        std::fs::remove_dir_all(&folder).unwrap();
+        assert!(
+            matches!(err, ParseError::UnsupportedVersion { got: 152, .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.code(), 1005);
+    }
 
-    let future_vec = new_all_person_list.into_iter().map(|info| {
-        let cloned_folder_path = folder_path.clone();
-        return tokio::spawn(
-            async move { save_person_to_file(&cloned_folder_path, info.0, &info.1) },
+    #[test]
+    fn read_v153_person() {
+        let (_, content) = read_fixture_list("person/v153")
+            .into_iter()
+            .find(|(name, _)| name == "basic.person")
+            .unwrap();
+
+        let person = extract_person_any_version(&content).unwrap();
+
+        assert_eq!(person.version, 154);
+        assert_eq!(person.block, "11 11");
+        assert_eq!(person.squad_size_setting, -1.0);
+        assert_eq!(person.item_list.len(), 1);
+
+        // 1.53 中逐件存放的物品按 key 合并为 item_group, 顺序与原存档一致
+        let backpack_list: Vec<(&str, i8, i32, i32)> = person
+            .backpack_item_list
+            .iter()
+            .map(|item| (item.key.as_str(), item.class, item.index, item.amount))
+            .collect();
+        assert_eq!(
+            backpack_list,
+            [("m9.weapon", 0, 1, 1), ("a.carry_item", 3, 2, 2)]
         );
-    });
+        assert_eq!(person.stash_item_list.len(), 1);
+        assert_eq!(person.stash_item_list[0].amount, 2);
+
+        let saved = save_person(&person).unwrap();
+        assert!(saved.contains(r#"version="154""#));
+        assert!(saved.contains(r#"custom_rank="veteran""#));
+        assert!(saved.contains(r#"<item_group class="3" index="2" key="a.carry_item" amount="2""#));
+        // 1.53 物品上未识别的属性保留在合并后的 item_group 中
+        assert!(saved.contains(r#"mod_tag="gold""#));
+    }
 
-    futures::future::try_join_all(future_vec).await?;
+    #[test]
+    fn merge_item_group() {
+        let item = |class: i8, index: i32, key: &str, amount: i32| ItemGroupTag {
+            class,
+            index,
+            key: key.to_string(),
+            amount,
+            ..ItemGroupTag::default()
+        };
+
+        let item_list = merge_item_group_list(vec![
+            item(0, 1, "m9.weapon", 1),
+            item(3, 2, "a.carry_item", 1),
+            item(0, 1, "m9.weapon", 2),
+            item(0, 2, "m9.weapon", 1),
+            item(3, 2, "a.carry_item", 4),
+            item(0, 3, "m9.weapon", i32::MAX),
+            item(0, 3, "m9.weapon", 1),
+            ItemGroupTag {
+                extra: XmlExtra {
+                    attr_list: vec![XmlAttr {
+                        key: "mod_tag".to_string(),
+                        value: "1".to_string(),
+                        position: 0,
+                    }],
+                    ..XmlExtra::default()
+                },
+                ..item(3, 2, "a.carry_item", 1)
+            },
+        ]);
+
+        let item_list: Vec<(i8, i32, &str, i32)> = item_list
+            .iter()
+            .map(|item| (item.class, item.index, item.key.as_str(), item.amount))
+            .collect();
+
+        // index 或 extra 不同的物品组不合并, 顺序保持首次出现的位置, 数量不溢出
+        assert_eq!(
+            item_list,
+            [
+                (0, 1, "m9.weapon", 3),
+                (3, 2, "a.carry_item", 5),
+                (0, 2, "m9.weapon", 1),
+                (0, 3, "m9.weapon", i32::MAX),
+                (3, 2, "a.carry_item", 1)
+            ]
+        );
+    }
 
-    Ok(err_profile_id_vec)
+    #[test]
+    fn insert_backpack_merge_existing() {
+        let (_, content) = read_fixture_list("person")
+            .into_iter()
+            .find(|(name, _)| name == "basic.person")
+            .unwrap();
+
+        let mut person = extract_person_any_version(&content).unwrap();
+        person.backpack_item_list = vec![ItemGroupTag {
+            class: 0,
+            index: 1,
+            key: "m9.weapon".to_string(),
+            amount: 1,
+            ..ItemGroupTag::default()
+        }];
+        let item_list = person.backpack_item_list.clone();
+
+        // 默认合并到已有的物品组
+        let new_person = insert_backpack_item_list(1, &person, &item_list, false).unwrap();
+        assert_eq!(new_person.backpack_item_list.len(), 1);
+        assert_eq!(new_person.backpack_item_list[0].amount, 2);
+
+        // append 时追加新的物品组
+        let new_person = insert_backpack_item_list(1, &person, &item_list, true).unwrap();
+        assert_eq!(new_person.backpack_item_list.len(), 2);
+    }
 }
@@ -3,22 +3,34 @@ use super::model::{
     InsertSelectedPersonBackpackReq, ItemGroupTag, Person, UpdateAllPersonSoldierGroupReq,
     UpdatePersonReq, UpdateSelectedPersonSoldierGroupReq,
 };
+use crate::audit::utils::{audit_person_change, audit_person_list_change};
+use crate::auth::model::{ApiKeyScope, Identity, Role};
+use crate::auth::utils::{check_profile_access, check_role_or_scope};
+use crate::backup::model::BackupKind;
 use crate::model::ResponseJson;
 use crate::person::async_extract::{
     async_extract_all_person, async_extract_all_person_and_profiles, async_extract_selected_person,
+    async_get_all_person_id,
 };
 use crate::person::extract::extract_person;
 use crate::person::model::{
-    DeleteSelectedPersonItemListReq, GroupInfo, ResetXpReq, UpdatePersonSoldierGroupRes,
+    CompactInventoryRes, DeleteSelectedPersonItemListReq, GroupInfo, InsertItemQuery,
+    MergeItemQuery, ResetXpReq, UpdatePersonSoldierGroupRes,
 };
 use crate::person::save::{
-    delete_person_item_list_to_file, insert_person_list_backpack_to_file, save_person_to_file,
-    update_person_list_soldider_group_to_file,
+    delete_person_item_list_to_file, insert_person_list_backpack_to_file, merge_item_group_list,
+    save_person_to_file_with_backup, update_person_list_soldider_group_to_file,
 };
+use crate::utils::{
+    check_file_if_match, check_upload_file_name, copy_file_atomic, get_file_etag, UploadTempFolder,
+};
+use crate::xml::error::parse_error_response;
 use crate::AppData;
 use actix_files::NamedFile;
 use actix_multipart::Multipart;
-use actix_web::{get, post, web, HttpResponse, Responder, Result};
+use actix_web::error::InternalError;
+use actix_web::http::header::{ETag, IfMatch};
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError, Result};
 use futures_util::TryStreamExt as _;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
@@ -35,6 +47,7 @@ pub fn person_config(cfg: &mut web::ServiceConfig) {
             .service(update_backpack)
             .service(update_stash)
             .service(update_group_type)
+            .service(compact_inventory)
             .service(insert_all_person_backpack)
             .service(insert_selected_person_backpack)
             .service(delete_item_list)
@@ -48,26 +61,49 @@ pub fn person_config(cfg: &mut web::ServiceConfig) {
 
 #[instrument]
 #[get("/query/{id}")]
-async fn query_person(config: web::Data<AppData>, id: web::Path<(u64,)>) -> impl Responder {
+async fn query_person(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> impl Responder {
     info!("");
-    let res = extract_person(id.into_inner().0, &config.rwr_profile_folder_path);
+    if let Err(res) = check_profile_access(&identity, id.0) {
+        return res;
+    }
+
+    let query_id = id.into_inner().0;
+    let etag = get_file_etag(format!(
+        "{}/{}.person",
+        &config.rwr_profile_folder_path, query_id
+    ));
+    let res = extract_person(query_id, &config.rwr_profile_folder_path);
 
     match res {
         Ok(person) => {
             info!("query res, person: {:?}", person);
-            HttpResponse::Ok().json(person)
+
+            let mut res = HttpResponse::Ok();
+
+            if let Ok(etag) = etag {
+                res.insert_header(ETag(etag));
+            }
+
+            res.json(person)
         }
         Err(err) => {
             error!("extract err: {:?}", err);
-            HttpResponse::NotFound().json(ResponseJson::default().set_err_msg(&err.to_string()))
+            err.error_response()
         }
     }
 }
 
 #[instrument]
 #[get("/query_all")]
-async fn query_all_person(config: web::Data<AppData>) -> impl Responder {
+async fn query_all_person(config: web::Data<AppData>, identity: Identity) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let cloned_folder_path = config.rwr_profile_folder_path.clone();
     return match async_extract_all_person_and_profiles(cloned_folder_path).await {
@@ -87,10 +123,15 @@ async fn query_all_person(config: web::Data<AppData>) -> impl Responder {
 #[post("/update/{id}")]
 async fn update_person(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
     data: web::Json<UpdatePersonReq>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
     let query_id = id.into_inner().0;
     let source = extract_person(query_id, &config.rwr_profile_folder_path);
 
@@ -104,9 +145,26 @@ async fn update_person(
 
 #[instrument]
 #[post("/reset_xp_5_stars/{id}")]
-async fn reset_xp_5_starts(config: web::Data<AppData>, id: web::Path<(u64,)>) -> impl Responder {
+async fn reset_xp_5_starts(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
+) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
     let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
     let source = extract_person(query_id, &config.rwr_profile_folder_path);
 
     return match source {
@@ -114,14 +172,31 @@ async fn reset_xp_5_starts(config: web::Data<AppData>, id: web::Path<(u64,)>) ->
             let new_person = Person {
                 max_authority_reached: 11.098661,
                 authority: 11.098661,
-                ..person
+                ..person.clone()
             };
 
             info!("new_person: {:?}", new_person);
 
-            match save_person_to_file(&config.rwr_profile_folder_path, query_id, &new_person) {
-                Ok(_) => HttpResponse::Ok()
-                    .json(ResponseJson::default().set_successful_msg("update stash successful")),
+            match save_person_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_person,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/reset_xp_5_stars",
+                        query_id,
+                        Some(&person),
+                        &new_person,
+                    )
+                    .await;
+
+                    HttpResponse::Ok()
+                        .json(ResponseJson::default().set_successful_msg("update stash successful"))
+                }
                 Err(err) => {
                     error!("save person error {:?}", err);
                     HttpResponse::BadRequest()
@@ -131,8 +206,7 @@ async fn reset_xp_5_starts(config: web::Data<AppData>, id: web::Path<(u64,)>) ->
         }
         Err(err) => {
             error!("merge person error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("save person error"))
+            err.error_response()
         }
     };
 }
@@ -141,11 +215,25 @@ async fn reset_xp_5_starts(config: web::Data<AppData>, id: web::Path<(u64,)>) ->
 #[post("/reset_xp/{id}")]
 async fn reset_xp(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
     data: web::Json<ResetXpReq>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
     let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
     let source = extract_person(query_id, &config.rwr_profile_folder_path);
     let data: ResetXpReq = data.into_inner();
 
@@ -154,14 +242,31 @@ async fn reset_xp(
             let new_person = Person {
                 max_authority_reached: data.authority,
                 authority: data.authority,
-                ..person
+                ..person.clone()
             };
 
             info!("new_person: {:?}", new_person);
 
-            match save_person_to_file(&config.rwr_profile_folder_path, query_id, &new_person) {
-                Ok(_) => HttpResponse::Ok()
-                    .json(ResponseJson::default().set_successful_msg("update stash successful")),
+            match save_person_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_person,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/reset_xp",
+                        query_id,
+                        Some(&person),
+                        &new_person,
+                    )
+                    .await;
+
+                    HttpResponse::Ok()
+                        .json(ResponseJson::default().set_successful_msg("update stash successful"))
+                }
                 Err(err) => {
                     error!("save person error {:?}", err);
                     HttpResponse::BadRequest()
@@ -171,8 +276,7 @@ async fn reset_xp(
         }
         Err(err) => {
             error!("merge person error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("save person error"))
+            err.error_response()
         }
     };
 }
@@ -181,21 +285,42 @@ async fn reset_xp(
 #[post("/update_backpack/{id}")]
 async fn update_backpack(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
+    query: web::Query<MergeItemQuery>,
     data: web::Json<Vec<ItemGroupTag>>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
     let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
     let source = extract_person(query_id, &config.rwr_profile_folder_path);
 
     return match source {
         Ok(person) => {
+            let item_list = data.into_inner();
             let new_person = Person {
-                backpack_item_list: data.into_inner(),
-                ..person
+                backpack_item_list: if query.merge {
+                    merge_item_group_list(item_list)
+                } else {
+                    item_list
+                },
+                ..person.clone()
             };
 
-            if new_person.backpack_item_list.len() > <u16 as Into<usize>>::into(new_person.backpack_hard_capacity) {
+            if new_person.backpack_item_list.len() > usize::from(new_person.backpack_hard_capacity)
+            {
                 error!("backpack item over 255");
                 return HttpResponse::BadRequest()
                     .json(ResponseJson::default().set_err_msg("backpack overload 255"));
@@ -203,9 +328,26 @@ async fn update_backpack(
 
             info!("new_person: {:?}", new_person);
 
-            match save_person_to_file(&config.rwr_profile_folder_path, query_id, &new_person) {
-                Ok(_) => HttpResponse::Ok()
-                    .json(ResponseJson::default().set_successful_msg("update stash successful")),
+            match save_person_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_person,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/update_backpack",
+                        query_id,
+                        Some(&person),
+                        &new_person,
+                    )
+                    .await;
+
+                    HttpResponse::Ok()
+                        .json(ResponseJson::default().set_successful_msg("update stash successful"))
+                }
                 Err(err) => {
                     error!("save person error {:?}", err);
                     HttpResponse::BadRequest()
@@ -215,8 +357,7 @@ async fn update_backpack(
         }
         Err(err) => {
             error!("merge person error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("merge person error"))
+            err.error_response()
         }
     };
 }
@@ -225,21 +366,41 @@ async fn update_backpack(
 #[post("/update_stash/{id}")]
 async fn update_stash(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
+    query: web::Query<MergeItemQuery>,
     data: web::Json<Vec<ItemGroupTag>>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
     let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
     let source = extract_person(query_id, &config.rwr_profile_folder_path);
 
     return match source {
         Ok(person) => {
+            let item_list = data.into_inner();
             let new_person = Person {
-                stash_item_list: data.into_inner(),
-                ..person
+                stash_item_list: if query.merge {
+                    merge_item_group_list(item_list)
+                } else {
+                    item_list
+                },
+                ..person.clone()
             };
 
-            if new_person.stash_item_list.len() > <u16 as Into<usize>>::into(new_person.stash_hard_capacity) {
+            if new_person.stash_item_list.len() > usize::from(new_person.stash_hard_capacity) {
                 error!("stash item over 300");
                 return HttpResponse::BadRequest()
                     .json(ResponseJson::default().set_err_msg("stash overload 300"));
@@ -247,9 +408,26 @@ async fn update_stash(
 
             info!("new_person: {:?}", new_person);
 
-            match save_person_to_file(&config.rwr_profile_folder_path, query_id, &new_person) {
-                Ok(_) => HttpResponse::Ok()
-                    .json(ResponseJson::default().set_successful_msg("update stash successful")),
+            match save_person_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_person,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/update_stash",
+                        query_id,
+                        Some(&person),
+                        &new_person,
+                    )
+                    .await;
+
+                    HttpResponse::Ok()
+                        .json(ResponseJson::default().set_successful_msg("update stash successful"))
+                }
                 Err(err) => {
                     error!("save person error {:?}", err);
                     HttpResponse::BadRequest()
@@ -259,8 +437,86 @@ async fn update_stash(
         }
         Err(err) => {
             error!("merge person error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("merge person error"))
+            err.error_response()
+        }
+    };
+}
+
+#[instrument]
+#[post("/compact_inventory/{id}")]
+async fn compact_inventory(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
+    let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
+    let source = extract_person(query_id, &config.rwr_profile_folder_path);
+
+    return match source {
+        Ok(person) => {
+            let new_person = Person {
+                backpack_item_list: merge_item_group_list(person.backpack_item_list.clone()),
+                stash_item_list: merge_item_group_list(person.stash_item_list.clone()),
+                ..person.clone()
+            };
+
+            let res = CompactInventoryRes {
+                backpack_before: person.backpack_item_list.len(),
+                backpack_after: new_person.backpack_item_list.len(),
+                stash_before: person.stash_item_list.len(),
+                stash_after: new_person.stash_item_list.len(),
+            };
+
+            // 没有可合并的物品组时不写入存档
+            if res.backpack_before == res.backpack_after && res.stash_before == res.stash_after {
+                return HttpResponse::Ok().json(res);
+            }
+
+            match save_person_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_person,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    info!("compact inventory {} successful, {:?}", query_id, res);
+
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/compact_inventory",
+                        query_id,
+                        Some(&person),
+                        &new_person,
+                    )
+                    .await;
+
+                    HttpResponse::Ok().json(res)
+                }
+                Err(err) => {
+                    error!("save person error {:?}", err);
+                    HttpResponse::BadRequest()
+                        .json(ResponseJson::default().set_err_msg("save person error"))
+                }
+            }
+        }
+        Err(err) => {
+            error!("compact inventory error {:?}", err);
+            err.error_response()
         }
     };
 }
@@ -269,26 +525,57 @@ async fn update_stash(
 #[post("/update_group_type/{id}")]
 async fn update_group_type(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
     data: web::Json<GroupInfo>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
     let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
     let source = extract_person(query_id, &config.rwr_profile_folder_path);
 
     return match source {
         Ok(person) => {
             let new_person = Person {
                 soldier_group_name: data.into_inner().group_type,
-                ..person
+                ..person.clone()
             };
 
             info!("new_person: {:?}", new_person);
 
-            match save_person_to_file(&config.rwr_profile_folder_path, query_id, &new_person) {
-                Ok(_) => HttpResponse::Ok().json(
-                    ResponseJson::default().set_successful_msg("update group type successful"),
-                ),
+            match save_person_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_person,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/update_group_type",
+                        query_id,
+                        Some(&person),
+                        &new_person,
+                    )
+                    .await;
+
+                    HttpResponse::Ok().json(
+                        ResponseJson::default().set_successful_msg("update group type successful"),
+                    )
+                }
                 Err(err) => {
                     error!("save person error {:?}", err);
                     HttpResponse::BadRequest()
@@ -298,8 +585,7 @@ async fn update_group_type(
         }
         Err(err) => {
             error!("merge person error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("merge person error"))
+            err.error_response()
         }
     };
 }
@@ -308,24 +594,49 @@ async fn update_group_type(
 #[post("/insert_all_person_backpack")]
 async fn insert_all_person_backpack(
     config: web::Data<AppData>,
+    identity: Identity,
+    query: web::Query<InsertItemQuery>,
     data: web::Json<Vec<ItemGroupTag>>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Admin, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let insert_backpack_item_list = data.into_inner();
 
     let folder_clone = config.rwr_profile_folder_path.clone();
 
+    let _lock_list = match async_get_all_person_id(&config.rwr_profile_folder_path).await {
+        Ok(id_list) => config.profile_lock_registry.lock_list(&id_list).await,
+        Err(err) => {
+            error!("get all person id error {:?}", err);
+            return HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("get all person id error"));
+        }
+    };
+
     return match async_extract_all_person(folder_clone).await {
         Ok(all_person_list) => {
             match insert_person_list_backpack_to_file(
                 &config.rwr_profile_folder_path,
                 &all_person_list,
                 &insert_backpack_item_list,
+                query.append,
+                &config.backup_store,
             )
             .await
             {
-                Ok(()) => {
+                Ok(new_all_person_list) => {
+                    audit_person_list_change(
+                        &config,
+                        &identity,
+                        "/person/insert_all_person_backpack",
+                        &all_person_list,
+                        &new_all_person_list,
+                    )
+                    .await;
+
                     info!(
                         "inser all person backpack success, backpack_item_list: {:?}",
                         insert_backpack_item_list
@@ -344,8 +655,10 @@ async fn insert_all_person_backpack(
         }
         Err(err) => {
             error!("update all person backpack error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("update all person backpack error"))
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest()
+                    .json(ResponseJson::default().set_err_msg("update all person backpack error"))
+            })
         }
     };
 }
@@ -354,14 +667,23 @@ async fn insert_all_person_backpack(
 #[post("/insert_selected_person_backpack")]
 async fn insert_selected_person_backpack(
     config: web::Data<AppData>,
+    identity: Identity,
+    query: web::Query<InsertItemQuery>,
     data: web::Json<InsertSelectedPersonBackpackReq>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let insert_data_pre = data.into_inner();
 
     let insert_backpack_item_list = insert_data_pre.backpack_item_list;
     let profile_id_list = insert_data_pre.profile_id_list;
+    let _lock_list = config
+        .profile_lock_registry
+        .lock_list(&profile_id_list)
+        .await;
 
     let folder_clone = config.rwr_profile_folder_path.clone();
 
@@ -371,10 +693,21 @@ async fn insert_selected_person_backpack(
                 &config.rwr_profile_folder_path,
                 &all_person_list,
                 &insert_backpack_item_list,
+                query.append,
+                &config.backup_store,
             )
             .await
             {
-                Ok(()) => {
+                Ok(new_all_person_list) => {
+                    audit_person_list_change(
+                        &config,
+                        &identity,
+                        "/person/insert_selected_person_backpack",
+                        &all_person_list,
+                        &new_all_person_list,
+                    )
+                    .await;
+
                     info!(
                         "inser selected person backpack success, backpack_item_list: {:?}",
                         insert_backpack_item_list
@@ -396,8 +729,11 @@ async fn insert_selected_person_backpack(
         }
         Err(err) => {
             error!("update selected person backpack error {:?}", err);
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("update selected person backpack error"))
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest().json(
+                    ResponseJson::default().set_err_msg("update selected person backpack error"),
+                )
+            })
         }
     };
 }
@@ -406,24 +742,47 @@ async fn insert_selected_person_backpack(
 #[post("/delete_item_list")]
 async fn delete_item_list(
     config: web::Data<AppData>,
+    identity: Identity,
     data: web::Json<Vec<String>>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Admin, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let item_list: Vec<String> = data.into_inner();
 
     let folder_clone = config.rwr_profile_folder_path.clone();
 
+    let _lock_list = match async_get_all_person_id(&config.rwr_profile_folder_path).await {
+        Ok(id_list) => config.profile_lock_registry.lock_list(&id_list).await,
+        Err(err) => {
+            error!("get all person id error {:?}", err);
+            return HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("get all person id error"));
+        }
+    };
+
     return match async_extract_all_person(folder_clone).await {
         Ok(all_person_list) => {
             return match delete_person_item_list_to_file(
                 &config.rwr_profile_folder_path,
                 &all_person_list,
                 &item_list,
+                &config.backup_store,
             )
             .await
             {
-                Ok(()) => {
+                Ok(new_all_person_list) => {
+                    audit_person_list_change(
+                        &config,
+                        &identity,
+                        "/person/delete_item_list",
+                        &all_person_list,
+                        &new_all_person_list,
+                    )
+                    .await;
+
                     info!("delete item list success, item_list: {:?}", item_list);
                     HttpResponse::Ok().json(
                         ResponseJson::default().set_successful_msg("delete item list successful"),
@@ -439,8 +798,10 @@ async fn delete_item_list(
         Err(err) => {
             error!("delete item list to file person error {:?}", err);
 
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("delete item list to person error"))
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest()
+                    .json(ResponseJson::default().set_err_msg("delete item list to person error"))
+            })
         }
     };
 }
@@ -449,14 +810,22 @@ async fn delete_item_list(
 #[post("/delete_selected_person_item_list")]
 async fn delete_selected_person_item_list(
     config: web::Data<AppData>,
+    identity: Identity,
     data: web::Json<DeleteSelectedPersonItemListReq>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let delete_data_pre = data.into_inner();
 
     let item_list = delete_data_pre.item_list;
     let profile_id_list = delete_data_pre.profile_id_list;
+    let _lock_list = config
+        .profile_lock_registry
+        .lock_list(&profile_id_list)
+        .await;
 
     let folder_clone = config.rwr_profile_folder_path.clone();
 
@@ -466,10 +835,20 @@ async fn delete_selected_person_item_list(
                 &config.rwr_profile_folder_path,
                 &all_person_list,
                 &item_list,
+                &config.backup_store,
             )
             .await
             {
-                Ok(()) => {
+                Ok(new_all_person_list) => {
+                    audit_person_list_change(
+                        &config,
+                        &identity,
+                        "/person/delete_selected_person_item_list",
+                        &all_person_list,
+                        &new_all_person_list,
+                    )
+                    .await;
+
                     info!("delete item list success, item_list: {:?}", item_list);
                     HttpResponse::Ok().json(
                         ResponseJson::default().set_successful_msg("delete item list successful"),
@@ -485,8 +864,10 @@ async fn delete_selected_person_item_list(
         Err(err) => {
             error!("delete item list to file person error {:?}", err);
 
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("delete item list to person error"))
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest()
+                    .json(ResponseJson::default().set_err_msg("delete item list to person error"))
+            })
         }
     };
 }
@@ -495,13 +876,26 @@ async fn delete_selected_person_item_list(
 #[post("/update_all_person_soldier_group")]
 async fn update_all_soldier_group(
     config: web::Data<AppData>,
+    identity: Identity,
     data: web::Json<UpdateAllPersonSoldierGroupReq>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Admin, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let cloned_folder_path = config.rwr_profile_folder_path.clone();
     let data: UpdateAllPersonSoldierGroupReq = data.into_inner();
 
+    let _lock_list = match async_get_all_person_id(&config.rwr_profile_folder_path).await {
+        Ok(id_list) => config.profile_lock_registry.lock_list(&id_list).await,
+        Err(err) => {
+            error!("get all person id error {:?}", err);
+            return HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("get all person id error"));
+        }
+    };
+
     return match async_extract_all_person(cloned_folder_path).await {
         Ok(all_person_list) => {
             match update_person_list_soldider_group_to_file(
@@ -509,12 +903,24 @@ async fn update_all_soldier_group(
                 &all_person_list,
                 &data.group,
                 data.cost,
+                &config.backup_store,
             )
             .await
             {
-                Ok(err_profile_id_list) => HttpResponse::Ok().json(UpdatePersonSoldierGroupRes {
-                    error_profile_list: err_profile_id_list,
-                }),
+                Ok((err_profile_id_list, new_all_person_list)) => {
+                    audit_person_list_change(
+                        &config,
+                        &identity,
+                        "/person/update_all_person_soldier_group",
+                        &all_person_list,
+                        &new_all_person_list,
+                    )
+                    .await;
+
+                    HttpResponse::Ok().json(UpdatePersonSoldierGroupRes {
+                        error_profile_list: err_profile_id_list,
+                    })
+                }
                 Err(err) => {
                     error!("update all person soldider group to file error {:?}", err);
                     HttpResponse::BadRequest().json(
@@ -527,8 +933,11 @@ async fn update_all_soldier_group(
         Err(err) => {
             error!("update all person soldider group error {:?}", err);
 
-            HttpResponse::BadRequest()
-                .json(ResponseJson::default().set_err_msg("update all person soldider group error"))
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest().json(
+                    ResponseJson::default().set_err_msg("update all person soldider group error"),
+                )
+            })
         }
     };
 }
@@ -537,12 +946,20 @@ async fn update_all_soldier_group(
 #[post("/update_selected_person_soldier_group")]
 async fn update_selected_soldier_group(
     config: web::Data<AppData>,
+    identity: Identity,
     data: web::Json<UpdateSelectedPersonSoldierGroupReq>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
     let cloned_folder_path = config.rwr_profile_folder_path.clone();
     let data: UpdateSelectedPersonSoldierGroupReq = data.into_inner();
+    let _lock_list = config
+        .profile_lock_registry
+        .lock_list(&data.profile_id_list)
+        .await;
 
     return match async_extract_selected_person(cloned_folder_path, data.profile_id_list).await {
         Ok(all_person_list) => {
@@ -551,12 +968,24 @@ async fn update_selected_soldier_group(
                 &all_person_list,
                 &data.group,
                 data.cost,
+                &config.backup_store,
             )
             .await
             {
-                Ok(err_profile_id_list) => HttpResponse::Ok().json(UpdatePersonSoldierGroupRes {
-                    error_profile_list: err_profile_id_list,
-                }),
+                Ok((err_profile_id_list, new_all_person_list)) => {
+                    audit_person_list_change(
+                        &config,
+                        &identity,
+                        "/person/update_selected_person_soldier_group",
+                        &all_person_list,
+                        &new_all_person_list,
+                    )
+                    .await;
+
+                    HttpResponse::Ok().json(UpdatePersonSoldierGroupRes {
+                        error_profile_list: err_profile_id_list,
+                    })
+                }
                 Err(err) => {
                     error!(
                         "update selected person soldider group to file error {:?}",
@@ -572,17 +1001,26 @@ async fn update_selected_soldier_group(
         Err(err) => {
             error!("update selected person soldider group error {:?}", err);
 
-            HttpResponse::BadRequest().json(
-                ResponseJson::default().set_err_msg("update selected person soldider group error"),
-            )
+            parse_error_response(&err).unwrap_or_else(|| {
+                HttpResponse::BadRequest().json(
+                    ResponseJson::default()
+                        .set_err_msg("update selected person soldider group error"),
+                )
+            })
         }
     };
 }
 
 #[instrument]
 #[get("/download/{id}")]
-async fn download_person(config: web::Data<AppData>, id: web::Path<(u64,)>) -> Result<NamedFile> {
+async fn download_person(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> Result<NamedFile> {
     info!("");
+    check_profile_access(&identity, id.0)
+        .map_err(|res| InternalError::from_response("forbidden", res))?;
 
     let id: u64 = id.into_inner().0;
     let path = format!("{}/{}.person", &config.rwr_profile_folder_path, id);
@@ -608,28 +1046,52 @@ async fn download_person(config: web::Data<AppData>, id: web::Path<(u64,)>) -> R
 #[post("/upload/{id}")]
 async fn upload_person(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, actix_web::Error> {
+    check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite)
+        .map_err(|res| InternalError::from_response("forbidden", res))?;
+
     let id: u64 = id.into_inner().0;
 
     info!("in upload person service, id: {}", id);
 
     let mut temp_file_name = Arc::new(Mutex::new(String::new()));
 
+    let temp_folder = match UploadTempFolder::create(&config.server_upload_temp_folder_path, id) {
+        Ok(temp_folder) => temp_folder,
+        Err(err) => {
+            error!("create {} person upload temp folder error: {:?}", id, err);
+
+            let custom_err = ResponseJson::default().set_err_msg("create upload temp folder error");
+
+            return Ok(HttpResponse::BadRequest().json(custom_err));
+        }
+    };
+
     // iterate over multipart stream
     while let Some(mut field) = payload.try_next().await? {
         // A multipart/form-data stream has to contain `content_disposition`
         let content_disposition = field.content_disposition();
 
-        let filename = content_disposition.get_filename().unwrap();
-        // .map_or_else(|| "temp.person");
+        let filename = content_disposition.get_filename().unwrap_or_default();
+
+        if let Err(err) = check_upload_file_name(filename) {
+            error!("upload {} person error: {:?}", id, err);
+
+            let custom_err = ResponseJson::default().set_err_msg(&err.to_string());
+
+            return Ok(HttpResponse::BadRequest().json(custom_err));
+        }
 
         let mut outer_file_name = Arc::clone(&temp_file_name);
         let mut outer_file_name = outer_file_name.lock().unwrap();
         *outer_file_name = String::from(filename);
 
-        let filepath = format!("{}/{}", &config.server_upload_temp_folder_path, &filename);
+        // 临时文件与目标文件均按 id 命名, 客户端文件名仅用于日志
+        let filepath = format!("{}/{}.person", &temp_folder.path, id);
         info!("filepath: {}", filepath);
 
         // File::create is blocking operation, use threadpool
@@ -697,36 +1159,74 @@ async fn upload_person(
     //     }
     // }
 
-    let temp_file_name = temp_file_name.lock().unwrap();
+    let temp_file_name = temp_file_name.lock().unwrap().clone();
+
+    if temp_file_name.is_empty() {
+        error!("upload {} person error: no file received", id);
+
+        let custom_err = ResponseJson::default().set_err_msg("no upload file received");
+
+        return Ok(HttpResponse::BadRequest().json(custom_err));
+    }
+
+    let _lock = config.profile_lock_registry.lock(id).await;
+
+    check_file_if_match(
+        format!("{}/{}.person", &config.rwr_profile_folder_path, id),
+        if_match.as_deref(),
+    )
+    .map_err(|res| InternalError::from_response("precondition failed", res))?;
+
     info!("Ready to validate filename: {}", &temp_file_name);
 
-    return match extract_person(id, &config.server_upload_temp_folder_path) {
+    return match extract_person(id, &temp_folder.path) {
         Ok(person) => {
-            if person.backpack_item_list.len() > <u16 as Into<usize>>::into(person.backpack_hard_capacity) {
+            if person.backpack_item_list.len() > usize::from(person.backpack_hard_capacity) {
                 let custom_err = ResponseJson::default().set_err_msg("person backpack over 255");
 
                 return Ok(HttpResponse::BadRequest().json(custom_err));
             }
 
-            if person.stash_item_list.len() > <u16 as Into<usize>>::into(person.stash_hard_capacity) {
+            if person.stash_item_list.len() > usize::from(person.stash_hard_capacity) {
                 let custom_err = ResponseJson::default().set_err_msg("person stash over 300");
 
                 return Ok(HttpResponse::BadRequest().json(custom_err));
             }
 
-            let from_path = format!(
-                "{}/{}",
-                &config.server_upload_temp_folder_path, temp_file_name
-            );
-            let target_path = format!("{}/{}", &config.rwr_profile_folder_path, temp_file_name);
+            let from_path = format!("{}/{}.person", &temp_folder.path, id);
+            let target_path = format!("{}/{}.person", &config.rwr_profile_folder_path, id);
+
+            let source_person = extract_person(id, &config.rwr_profile_folder_path).ok();
+
+            if let Err(err) =
+                config
+                    .backup_store
+                    .backup(&config.rwr_profile_folder_path, id, BackupKind::Person)
+            {
+                error!("backup {} person error: {:?}", id, err);
 
-            return match std::fs::copy(from_path, target_path) {
-                Ok(_) => Ok(HttpResponse::Ok()
-                    .json(
+                let custom_err = ResponseJson::default().set_err_msg("backup person error");
+
+                return Ok(HttpResponse::BadRequest().json(custom_err));
+            }
+
+            return match copy_file_atomic(from_path, target_path) {
+                Ok(_) => {
+                    audit_person_change(
+                        &config,
+                        &identity,
+                        "/person/upload",
+                        id,
+                        source_person.as_ref(),
+                        &person,
+                    )
+                    .await;
+
+                    Ok(HttpResponse::Ok().json(
                         ResponseJson::default()
                             .set_successful_msg("upload & replace person success"),
-                    )
-                    .into()),
+                    ))
+                }
                 Err(err) => {
                     let err_msg = format!("extract {} person error: {}", id, err.to_string());
                     error!("{}", err);
@@ -738,12 +1238,9 @@ async fn upload_person(
             };
         }
         Err(err) => {
-            let err_msg = format!("extract {} person error: {}", id, err.to_string());
-            error!("{}", err);
-
-            let custom_err = ResponseJson::default().set_err_msg(&err_msg);
+            error!("extract {} person error: {}", id, err);
 
-            Ok(HttpResponse::BadRequest().json(custom_err))
+            Ok(err.error_response())
         }
     };
 }
@@ -1,115 +1,159 @@
 // SPDX-License-Identifier: GPL-3.0-only
-use super::model::Profile;
-use anyhow::Result;
-use quick_xml::{events::Event, Reader};
-use std::str;
-use tracing::info;
+use super::model::{MonitorTag, Profile, Stats};
+use crate::constant::MAX_PROFILE_FILE_VERSION;
+use crate::xml::{
+    error::ParseError,
+    extract::extract_element_tree,
+    model::{XmlElement, XmlNode},
+};
+use std::fs;
+
+pub fn extract_profile(id: u64, folder_path: &str) -> Result<Profile, ParseError> {
+    let path = format!("{}/{}.profile", folder_path, id);
+
+    let content = fs::read_to_string(&path).map_err(|err| ParseError::from_io(&path, err))?;
+
+    let profile = extract_profile_from_str(&content)?;
+
+    if profile.game_version != u64::from(MAX_PROFILE_FILE_VERSION) {
+        return Err(ParseError::UnsupportedVersion {
+            expected: MAX_PROFILE_FILE_VERSION.into(),
+            got: profile.game_version,
+        });
+    }
+
+    Ok(profile)
+}
+
+// 未识别的属性与子节点保存在 extra 中, 保存时原样写回
+pub fn extract_profile_from_str(content: &str) -> Result<Profile, ParseError> {
+    let root = extract_element_tree(content)?;
+
+    if root.name != "profile" {
+        return Err(ParseError::MalformedXml {
+            position: 0,
+            message: format!("root element is not profile: {}", root.name),
+        });
+    }
 
-pub fn extract_profile(id: u64, folder_path: &str) -> Result<Profile> {
     let mut profile = Profile::default();
 
-    let path = format!("{}/{}.profile", folder_path, id);
+    for attr in root.attr_list {
+        match attr.key.as_str() {
+            "game_version" => {
+                profile.game_version = attr.parse()?;
+            }
+            "username" => {
+                profile.username = attr.value;
+            }
+            "digest" => {
+                profile.digest = attr.value;
+            }
+            "sid" => {
+                profile.sid = attr.value;
+            }
+            "rid" => {
+                profile.rid = attr.value;
+            }
+            "squad_tag" => profile.squad_tag = attr.value,
+            "color" => profile.color = attr.value,
+            _ => profile.extra.attr_list.push(attr),
+        }
+    }
 
-    let mut reader = Reader::from_file(path)?;
-
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(e)) => match e.name() {
-                b"profile" => {
-                    for attr in e.attributes() {
-                        let attr_unwrap_res = attr?;
-                        let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                        let attr_key = attr_unwrap_res.key;
-
-                        match attr_key {
-                            b"game_version" => {
-                                profile.game_version = attr_value.parse()?;
-                            }
-                            b"username" => {
-                                profile.username = attr_value;
-                            }
-                            b"sid" => {
-                                profile.sid = attr_value;
-                            }
-                            b"rid" => {
-                                profile.rid = attr_value;
-                            }
-                            b"squad_tag" => profile.squad_tag = attr_value,
-                            b"color" => profile.color = attr_value,
-                            _ => (),
-                        }
-                    }
-                }
-                b"stats" => {
-                    for attr in e.attributes() {
-                        let attr_unwrap_res = attr?;
-                        let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                        let attr_key = attr_unwrap_res.key;
-
-                        match attr_key {
-                            b"kills" => {
-                                profile.stats.kills = attr_value.parse()?;
-                            }
-                            b"deaths" => {
-                                profile.stats.deaths = attr_value.parse()?;
-                            }
-                            b"time_played" => {
-                                profile.stats.time_played = attr_value.parse()?;
-                            }
-                            b"player_kills" => {
-                                profile.stats.player_kills = attr_value.parse()?;
-                            }
-                            b"teamkills" => {
-                                profile.stats.team_kills = attr_value.parse()?;
-                            }
-
-                            b"longest_kill_streak" => {
-                                profile.stats.longest_kill_streak = attr_value.parse()?;
-                            }
-                            b"targets_destroyed" => {
-                                profile.stats.targets_destroyed = attr_value.parse()?;
-                            }
-                            b"vehicles_destroyed" => {
-                                profile.stats.vehicles_destroyed = attr_value.parse()?;
-                            }
-                            b"soldiers_healed" => {
-                                profile.stats.soldiers_healed = attr_value.parse()?;
-                            }
-                            b"times_got_healed" => {
-                                profile.stats.times_got_healed = attr_value.parse()?;
-                            }
-                            b"distance_moved" => {
-                                profile.stats.distance_moved = attr_value.parse()?;
-                            }
-                            b"shots_fired" => {
-                                profile.stats.shots_fired = attr_value.parse()?;
-                            }
-                            b"throwables_thrown" => {
-                                profile.stats.throwables_thrown = attr_value.parse()?;
-                            }
-                            b"rank_progression" => {
-                                profile.stats.rank_progression = attr_value.parse()?;
-                            }
-                            _ => (),
-                        }
-                    }
-                }
-                _ => (),
-            },
-            Ok(Event::Empty(e)) => {}
-            Ok(Event::End(e)) => {
-                // DEBUG
-                // println!("end e.name: {:?}", str::from_utf8(e.name())?);
-            }
-            Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {} : {:?}", reader.buffer_position(), e),
-            _ => (),
+    for (index, node) in root.child_list.into_iter().enumerate() {
+        match node {
+            XmlNode::Element(element) if element.name == "stats" => {
+                profile.stats = extract_stats(element)?;
+            }
+            node => profile.extra.push_child(index, node),
         }
     }
 
     Ok(profile)
 }
+
+fn extract_stats(element: XmlElement) -> Result<Stats, ParseError> {
+    let mut stats = Stats::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "kills" => {
+                stats.kills = attr.parse()?;
+            }
+            "deaths" => {
+                stats.deaths = attr.parse()?;
+            }
+            "time_played" => {
+                stats.time_played = attr.parse()?;
+            }
+            "player_kills" => {
+                stats.player_kills = attr.parse()?;
+            }
+            "teamkills" => {
+                stats.team_kills = attr.parse()?;
+            }
+            "longest_kill_streak" => {
+                stats.longest_kill_streak = attr.parse()?;
+            }
+            "targets_destroyed" => {
+                stats.targets_destroyed = attr.parse()?;
+            }
+            "vehicles_destroyed" => {
+                stats.vehicles_destroyed = attr.parse()?;
+            }
+            "soldiers_healed" => {
+                stats.soldiers_healed = attr.parse()?;
+            }
+            "times_got_healed" => {
+                stats.times_got_healed = attr.parse()?;
+            }
+            "distance_moved" => {
+                stats.distance_moved = attr.parse()?;
+            }
+            "shots_fired" => {
+                stats.shots_fired = attr.parse()?;
+            }
+            "throwables_thrown" => {
+                stats.throwables_thrown = attr.parse()?;
+            }
+            "rank_progression" => {
+                stats.rank_progression = attr.parse()?;
+            }
+            _ => stats.extra.attr_list.push(attr),
+        }
+    }
+
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        match node {
+            XmlNode::Element(element) if element.name == "monitor" => {
+                stats.monitor_list.push(extract_monitor(element)?);
+            }
+            node => stats.extra.push_child(index, node),
+        }
+    }
+
+    Ok(stats)
+}
+
+fn extract_monitor(element: XmlElement) -> Result<MonitorTag, ParseError> {
+    let mut monitor = MonitorTag::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "name" => {
+                monitor.name = attr.value;
+            }
+            "longest" => {
+                monitor.longest = attr.parse()?;
+            }
+            _ => monitor.extra.attr_list.push(attr),
+        }
+    }
+
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        monitor.extra.push_child(index, node);
+    }
+
+    Ok(monitor)
+}
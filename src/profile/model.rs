@@ -1,5 +1,18 @@
 // SPDX-License-Identifier: GPL-3.0-only
+use crate::constant::{MAX_SQUAD_TAG_LEN, SQUAD_TAG_SYMBOL_CHARS};
+use crate::xml::model::XmlExtra;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// 连杀/连死等统计, 位于 stats 下
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MonitorTag {
+    pub name: String,
+    pub longest: u64,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Stats {
@@ -17,17 +30,26 @@ pub struct Stats {
     pub shots_fired: u64,
     pub throwables_thrown: u64,
     pub rank_progression: f64,
+    #[serde(default)]
+    pub monitor_list: Vec<MonitorTag>,
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Profile {
     pub game_version: u64,
     pub username: String,
+    #[serde(default)]
+    pub digest: String,
     pub sid: String,
     pub rid: String,
     pub squad_tag: String,
     pub color: String,
-    pub stats: Stats, // TODO: 暂时只解析到此
+    pub stats: Stats,
+    // 未识别的属性与子节点
+    #[serde(default, skip_serializing_if = "XmlExtra::is_empty")]
+    pub extra: XmlExtra,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -53,6 +75,8 @@ impl Default for Stats {
             shots_fired: 0,
             throwables_thrown: 0,
             rank_progression: 0.0,
+            monitor_list: vec![],
+            extra: XmlExtra::default(),
         }
     }
 }
@@ -62,11 +86,115 @@ impl Default for Profile {
         Self {
             game_version: 0,
             username: String::new(),
+            digest: String::new(),
             sid: String::new(),
             rid: String::new(),
             squad_tag: String::new(),
             color: String::new(),
             stats: Stats::default(),
+            extra: XmlExtra::default(),
         }
     }
 }
+
+// 仅修改传入的字段, stats 中未传入的字段保持不变
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateProfileReq {
+    pub username: Option<String>,
+    pub squad_tag: Option<String>,
+    pub color: Option<String>,
+    pub stats: Option<Map<String, Value>>,
+}
+
+// color 格式: "r g b a", 各分量取值 0 ~ 1
+fn is_valid_color(color: &str) -> bool {
+    let part_list: Vec<&str> = color.split(' ').collect();
+
+    part_list.len() == 4
+        && part_list
+            .iter()
+            .all(|part| part.parse::<f32>().is_ok_and(|v| (0.0..=1.0).contains(&v)))
+}
+
+// 空字符串表示清除标签
+pub fn check_squad_tag(squad_tag: &str) -> Result<()> {
+    if squad_tag.chars().count() > MAX_SQUAD_TAG_LEN {
+        return Err(anyhow!(
+            "squad_tag too long, max length: {}",
+            MAX_SQUAD_TAG_LEN
+        ));
+    }
+
+    if let Some(c) = squad_tag
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !SQUAD_TAG_SYMBOL_CHARS.contains(*c))
+    {
+        return Err(anyhow!("squad_tag contains invalid char: {:?}", c));
+    }
+
+    Ok(())
+}
+
+impl UpdateProfileReq {
+    pub fn merge_into(self, profile: &Profile) -> Result<Profile> {
+        let mut new_profile = profile.clone();
+
+        if let Some(username) = self.username {
+            if username.trim().is_empty() {
+                return Err(anyhow!("username required"));
+            }
+            new_profile.username = username;
+        }
+
+        if let Some(squad_tag) = self.squad_tag {
+            check_squad_tag(&squad_tag)?;
+            new_profile.squad_tag = squad_tag;
+        }
+
+        if let Some(color) = self.color {
+            if !is_valid_color(&color) {
+                return Err(anyhow!("color format not correct: {}", color));
+            }
+            new_profile.color = color;
+        }
+
+        if let Some(stats) = self.stats {
+            let mut stats_value = serde_json::to_value(&profile.stats)?;
+
+            if let Value::Object(stats_map) = &mut stats_value {
+                for (key, value) in stats {
+                    if !stats_map.contains_key(&key) {
+                        return Err(anyhow!("unknown stats field: {}", key));
+                    }
+                    stats_map.insert(key, value);
+                }
+            }
+
+            new_profile.stats = serde_json::from_value(stats_value)?;
+        }
+
+        Ok(new_profile)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateAllProfileSquadTagReq {
+    pub squad_tag: String,
+    // 仅修改当前标签与之相同的存档, 如解散战队时清除该战队标签
+    pub old_squad_tag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateSelectedProfileSquadTagReq {
+    pub profile_id_list: Vec<u64>,
+    pub squad_tag: String,
+    pub old_squad_tag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct UpdateProfileSquadTagRes {
+    pub updated_profile_list: Vec<u64>,
+    // 标签与 old_squad_tag 不符或无需修改
+    pub skipped_profile_list: Vec<u64>,
+    pub error_profile_list: Vec<u64>,
+}
@@ -1,40 +1,270 @@
 // SPDX-License-Identifier: GPL-3.0-only
 use anyhow::Result;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+use quick_xml::Writer;
+use std::io::Cursor;
+use tracing::error;
 
-use super::model::Profile;
+use super::extract::extract_profile;
+use super::model::{Profile, UpdateProfileSquadTagRes};
+use crate::backup::{model::BackupKind, store::BackupStore};
+use crate::utils::write_file_atomic;
+use crate::xml::save::{push_extra_attr_list, write_leaf_element, ExtraChildWriter};
 
+pub fn save_profile(p: &Profile) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 4);
+
+    // 游戏写入的 profile 文件带有 xml 声明
+    writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"utf-8"), None)))?;
+
+    let mut profile_tag = BytesStart::owned_name("profile");
+
+    profile_tag.push_attribute(("game_version", p.game_version.to_string().as_str()));
+    profile_tag.push_attribute(("username", p.username.as_str()));
+    profile_tag.push_attribute(("digest", p.digest.as_str()));
+    profile_tag.push_attribute(("sid", p.sid.as_str()));
+    profile_tag.push_attribute(("rid", p.rid.as_str()));
+    profile_tag.push_attribute(("squad_tag", p.squad_tag.as_str()));
+    profile_tag.push_attribute(("color", p.color.as_str()));
+    push_extra_attr_list(&mut profile_tag, &p.extra);
+
+    writer.write_event(Event::Start(profile_tag))?;
+
+    // 未识别的子节点按原始位置穿插写回
+    let mut profile_child_writer = ExtraChildWriter::new(&p.extra);
+
+    let s = &p.stats;
+    let mut stats_tag = BytesStart::owned_name("stats");
+
+    stats_tag.push_attribute(("kills", s.kills.to_string().as_str()));
+    stats_tag.push_attribute(("deaths", s.deaths.to_string().as_str()));
+    stats_tag.push_attribute(("time_played", s.time_played.to_string().as_str()));
+    stats_tag.push_attribute(("player_kills", s.player_kills.to_string().as_str()));
+    stats_tag.push_attribute(("teamkills", s.team_kills.to_string().as_str()));
+    stats_tag.push_attribute((
+        "longest_kill_streak",
+        s.longest_kill_streak.to_string().as_str(),
+    ));
+    stats_tag.push_attribute((
+        "targets_destroyed",
+        s.targets_destroyed.to_string().as_str(),
+    ));
+    stats_tag.push_attribute((
+        "vehicles_destroyed",
+        s.vehicles_destroyed.to_string().as_str(),
+    ));
+    stats_tag.push_attribute(("soldiers_healed", s.soldiers_healed.to_string().as_str()));
+    stats_tag.push_attribute(("times_got_healed", s.times_got_healed.to_string().as_str()));
+    stats_tag.push_attribute(("distance_moved", s.distance_moved.to_string().as_str()));
+    stats_tag.push_attribute(("shots_fired", s.shots_fired.to_string().as_str()));
+    stats_tag.push_attribute((
+        "throwables_thrown",
+        s.throwables_thrown.to_string().as_str(),
+    ));
+    stats_tag.push_attribute(("rank_progression", s.rank_progression.to_string().as_str()));
+    push_extra_attr_list(&mut stats_tag, &s.extra);
+
+    profile_child_writer.write_before_known(&mut writer)?;
+
+    if s.monitor_list.is_empty() && s.extra.child_list.is_empty() {
+        writer.write_event(Event::Empty(stats_tag))?;
+    } else {
+        writer.write_event(Event::Start(stats_tag))?;
+
+        let mut stats_child_writer = ExtraChildWriter::new(&s.extra);
+
+        for monitor in s.monitor_list.iter() {
+            let mut monitor_tag = BytesStart::owned_name("monitor");
+
+            monitor_tag.push_attribute(("name", monitor.name.as_str()));
+            monitor_tag.push_attribute(("longest", monitor.longest.to_string().as_str()));
+
+            stats_child_writer.write_before_known(&mut writer)?;
+            write_leaf_element(&mut writer, monitor_tag, &monitor.extra)?;
+        }
+
+        stats_child_writer.write_rest(&mut writer)?;
+
+        writer.write_event(Event::End(BytesEnd::borrowed(b"stats")))?;
+    }
+
+    profile_child_writer.write_rest(&mut writer)?;
+
+    writer.write_event(Event::End(BytesEnd::borrowed(b"profile")))?;
+
+    let result = String::from_utf8(writer.into_inner().into_inner())?;
+
+    Ok(result)
+}
+
+pub fn save_profile_to_file(path: &str, id: u64, profile: &Profile) -> Result<()> {
+    let res_profile_str = save_profile(profile)?;
+
+    let target_path = format!("{}/{}.profile", path, id);
+
+    write_file_atomic(target_path, res_profile_str.as_bytes())
+}
+
+// 保存前先备份当前存档
+pub fn save_profile_to_file_with_backup(
+    path: &str,
+    id: u64,
+    profile: &Profile,
+    backup_store: &BackupStore,
+) -> Result<()> {
+    backup_store.backup(path, id, BackupKind::Profile)?;
+
+    save_profile_to_file(path, id, profile)
+}
+
+// 返回各存档的处理结果, 以及实际修改的 (id, 修改前, 修改后)
 pub async fn update_profile_list_squad_tag_to_file(
     path: &str,
-    all_profile_list: &Vec<(u64, Profile)>,
+    profile_id_list: &[u64],
     squad_tag: &str,
-) -> Result<Vec<u64>> {
-    let mut err_profile_id_vec = vec![];
+    old_squad_tag: Option<&str>,
+    backup_store: &BackupStore,
+) -> Result<(UpdateProfileSquadTagRes, Vec<(u64, Profile, Profile)>)> {
+    let future_vec = profile_id_list.iter().map(|id| {
+        let id = *id;
+        let cloned_folder_path = path.to_string();
+        let cloned_backup_store = backup_store.clone();
+        let squad_tag = squad_tag.to_string();
+        let old_squad_tag = old_squad_tag.map(|tag| tag.to_string());
 
-    let new_all_profile_list: Vec<(u64, Profile)> = all_profile_list
-        .into_iter()
-        .map(|info| {
-            let (_id, _profile) = info;
-            let id: u64 = _id.clone();
-            let mut new_profile: Profile = _profile.clone();
+        tokio::spawn(async move {
+            let profile = extract_profile(id, &cloned_folder_path)?;
 
-            new_profile.squad_tag = squad_tag.to_string();
+            let is_skip = profile.squad_tag == squad_tag
+                || old_squad_tag.is_some_and(|tag| tag != profile.squad_tag);
 
-            (id, new_profile)
-        })
-        .collect();
+            if is_skip {
+                return Ok(None);
+            }
 
-    let folder_path = path.to_string();
+            let new_profile = Profile {
+                squad_tag,
+                ..profile.clone()
+            };
 
-    let future_vec = new_all_profile_list.into_iter().map(|info| {
-        let cloned_folder_path = folder_path.clone();
+            save_profile_to_file_with_backup(
+                &cloned_folder_path,
+                id,
+                &new_profile,
+                &cloned_backup_store,
+            )?;
 
-        return tokio::spawn(async move {
-            // TODO
-            // save_profile_to_file(&cloned_folder_path, info.0, &info.1)
-        });
+            anyhow::Ok(Some((profile, new_profile)))
+        })
     });
 
-    futures::future::try_join_all(future_vec).await?;
+    let mut res = UpdateProfileSquadTagRes::default();
+    let mut change_list = vec![];
+
+    for (id, task_res) in profile_id_list
+        .iter()
+        .zip(futures::future::try_join_all(future_vec).await?)
+    {
+        match task_res {
+            Ok(Some((profile, new_profile))) => {
+                res.updated_profile_list.push(*id);
+                change_list.push((*id, profile, new_profile));
+            }
+            Ok(None) => res.skipped_profile_list.push(*id),
+            Err(err) => {
+                error!("profile id: {} update squad_tag error: {:?}", id, err);
+                res.error_profile_list.push(*id);
+            }
+        }
+    }
+
+    Ok((res, change_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::save_profile;
+    use crate::profile::{extract::extract_profile_from_str, model::UpdateProfileReq};
+    use crate::xml::extract::{extract_element_tree, read_fixture_list};
+    use serde_json::json;
+
+    #[test]
+    fn round_trip_fixture_list() {
+        let fixture_list = read_fixture_list("profile");
+        assert!(!fixture_list.is_empty());
+
+        for (name, content) in fixture_list {
+            let profile = extract_profile_from_str(&content).unwrap();
+            let saved = save_profile(&profile).unwrap();
+
+            let mut source_tree = extract_element_tree(&content).unwrap();
+            let mut saved_tree = extract_element_tree(&saved).unwrap();
+            source_tree.normalize();
+            saved_tree.normalize();
+
+            assert_eq!(source_tree, saved_tree, "fixture: {}", name);
+
+            // 保存结果再次读写后逐字节一致
+            let resaved = save_profile(&extract_profile_from_str(&saved).unwrap()).unwrap();
+
+            assert_eq!(saved, resaved, "fixture: {}", name);
+        }
+    }
+
+    #[test]
+    fn update_keeps_unknown_xml() {
+        let (_, content) = read_fixture_list("profile")
+            .into_iter()
+            .find(|(name, _)| name == "mod_extra.profile")
+            .unwrap();
+
+        let profile = extract_profile_from_str(&content).unwrap();
+
+        let req: UpdateProfileReq = serde_json::from_value(json!({
+            "squad_tag": "[NEW]",
+            "color": "0.1 0.2 0.3 1",
+            "stats": { "kills": 100, "rank_progression": 0.25 }
+        }))
+        .unwrap();
+
+        let new_profile = req.merge_into(&profile).unwrap();
+
+        assert_eq!(new_profile.username, profile.username);
+        assert_eq!(new_profile.stats.kills, 100);
+        assert_eq!(new_profile.stats.deaths, profile.stats.deaths);
+        assert_eq!(new_profile.stats.monitor_list.len(), 2);
+
+        let saved = save_profile(&new_profile).unwrap();
+
+        for expected in [
+            r#"squad_tag="[NEW]""#,
+            r#"banned="0""#,
+            r#"<achievement key="marathon" unlocked="1"/>"#,
+            r#"headshots="3""#,
+            r#"<monitor name="kill combo" longest="2" current="1"/>"#,
+            r#"<weapon_stats key="g36.weapon" kills="5"/>"#,
+            r#"<history season="3"/>"#,
+        ] {
+            assert!(saved.contains(expected), "missing: {}", expected);
+        }
+    }
+
+    #[test]
+    fn update_rejects_invalid_field() {
+        let profile = extract_profile_from_str(&read_fixture_list("profile")[0].1).unwrap();
+
+        for req in [
+            json!({ "color": "red" }),
+            json!({ "color": "0.1 0.2 2 1" }),
+            json!({ "username": " " }),
+            json!({ "squad_tag": "[TOO_LONG_TAG]" }),
+            json!({ "squad_tag": "a b" }),
+            json!({ "stats": { "unknown": 1 } }),
+            json!({ "stats": { "kills": -1 } }),
+        ] {
+            let req: UpdateProfileReq = serde_json::from_value(req).unwrap();
 
-    Ok(err_profile_id_vec)
+            assert!(req.merge_into(&profile).is_err());
+        }
+    }
 }
@@ -1,9 +1,26 @@
 // SPDX-License-Identifier: GPL-3.0-only
-use crate::profile::model::QueryAllCacheV2Res;
+use crate::audit::diff::diff_profile;
+use crate::audit::utils::{audit_change, audit_profile_list_change};
+use crate::auth::model::{ApiKeyScope, Identity, Role};
+use crate::auth::utils::{check_profile_access, check_role_or_scope};
+use crate::backup::model::BackupKind;
+use crate::person::async_extract::async_get_all_profile_id;
+use crate::profile::model::{
+    check_squad_tag, QueryAllCacheV2Res, UpdateAllProfileSquadTagReq, UpdateProfileReq,
+    UpdateSelectedProfileSquadTagReq,
+};
+use crate::profile::save::{
+    save_profile_to_file_with_backup, update_profile_list_squad_tag_to_file,
+};
+use crate::utils::{
+    check_file_if_match, check_upload_file_name, copy_file_atomic, get_file_etag, UploadTempFolder,
+};
 use crate::{model::ResponseJson, profile::extract::extract_profile, AppData};
 use actix_files::NamedFile;
 use actix_multipart::Multipart;
-use actix_web::{get, post, web, HttpResponse, Responder, Result};
+use actix_web::error::InternalError;
+use actix_web::http::header::{ETag, IfMatch};
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError, Result};
 use futures_util::{TryFutureExt, TryStreamExt as _};
 use std::io::Write;
 use std::sync::{Arc, Mutex};
@@ -15,6 +32,8 @@ pub fn profile_config(cfg: &mut web::ServiceConfig) {
             .service(query_profile)
             .service(query_all_cache)
             .service(query_all_cache_v2)
+            .service(update_all_profile_squad_tag)
+            .service(update_selected_profile_squad_tag)
             .service(update_profile)
             .service(download_profile)
             .service(upload_profile),
@@ -23,27 +42,49 @@ pub fn profile_config(cfg: &mut web::ServiceConfig) {
 
 #[instrument]
 #[get("/query/{id}")]
-async fn query_profile(config: web::Data<AppData>, id: web::Path<(u64,)>) -> impl Responder {
+async fn query_profile(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> impl Responder {
     info!("");
+    if let Err(res) = check_profile_access(&identity, id.0) {
+        return res;
+    }
 
-    let res = extract_profile(id.into_inner().0, &config.rwr_profile_folder_path);
+    let query_id = id.into_inner().0;
+    let etag = get_file_etag(format!(
+        "{}/{}.profile",
+        &config.rwr_profile_folder_path, query_id
+    ));
+    let res = extract_profile(query_id, &config.rwr_profile_folder_path);
 
     match res {
         Ok(profile) => {
             info!("query res, profile: {:?}", profile);
-            HttpResponse::Ok().json(profile)
+
+            let mut res = HttpResponse::Ok();
+
+            if let Ok(etag) = etag {
+                res.insert_header(ETag(etag));
+            }
+
+            res.json(profile)
         }
         Err(err) => {
             error!("extract error {:?}", err);
-            HttpResponse::NotFound().json(ResponseJson::default().set_err_msg(&err.to_string()))
+            err.error_response()
         }
     }
 }
 
 #[instrument]
 #[get("/query_all_cache")]
-async fn query_all_cache(config: web::Data<AppData>) -> impl Responder {
+async fn query_all_cache(config: web::Data<AppData>, identity: Identity) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Player, ApiKeyScope::CacheRead) {
+        return res;
+    }
 
     let data = config.snapshot_data.lock().await;
 
@@ -55,8 +96,11 @@ async fn query_all_cache(config: web::Data<AppData>) -> impl Responder {
 
 #[instrument]
 #[get("/query_all_cache_v2")]
-async fn query_all_cache_v2(config: web::Data<AppData>) -> impl Responder {
+async fn query_all_cache_v2(config: web::Data<AppData>, identity: Identity) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Player, ApiKeyScope::CacheRead) {
+        return res;
+    }
 
     let list_str = config.snapshot_str.lock().await;
     let snapshot_time = config.snapshot_time.lock().await;
@@ -70,38 +114,201 @@ async fn query_all_cache_v2(config: web::Data<AppData>) -> impl Responder {
     HttpResponse::Ok().json(res)
 }
 
-// TODO
 #[instrument]
 #[post("/update_all_profile_squad_tag")]
-async fn update_all_profile_squad_tag(config: web::Data<AppData>) -> impl Responder {
+async fn update_all_profile_squad_tag(
+    config: web::Data<AppData>,
+    identity: Identity,
+    data: web::Json<UpdateAllProfileSquadTagReq>,
+) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Admin, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
-    let cloned_folder_path = config.rwr_profile_folder_path.clone();
+    let data: UpdateAllProfileSquadTagReq = data.into_inner();
 
-    HttpResponse::Ok()
+    if let Err(err) = check_squad_tag(&data.squad_tag) {
+        return HttpResponse::BadRequest()
+            .json(ResponseJson::default().set_err_msg(&err.to_string()));
+    }
+
+    let profile_id_list = match async_get_all_profile_id(&config.rwr_profile_folder_path).await {
+        Ok(id_list) => id_list,
+        Err(err) => {
+            error!("get all profile id error {:?}", err);
+            return HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("get all profile id error"));
+        }
+    };
+    let _lock_list = config
+        .profile_lock_registry
+        .lock_list(&profile_id_list)
+        .await;
+
+    return match update_profile_list_squad_tag_to_file(
+        &config.rwr_profile_folder_path,
+        &profile_id_list,
+        &data.squad_tag,
+        data.old_squad_tag.as_deref(),
+        &config.backup_store,
+    )
+    .await
+    {
+        Ok((res, change_list)) => {
+            audit_profile_list_change(
+                &config,
+                &identity,
+                "/profile/update_all_profile_squad_tag",
+                &change_list,
+            )
+            .await;
+
+            HttpResponse::Ok().json(res)
+        }
+        Err(err) => {
+            error!("update all profile squad tag error {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("update all profile squad tag error"))
+        }
+    };
 }
 
-// TODO
 #[instrument]
 #[post("/update_selected_profile_squad_tag")]
-async fn update_selected_profile_squad_tag(config: web::Data<AppData>) -> impl Responder {
+async fn update_selected_profile_squad_tag(
+    config: web::Data<AppData>,
+    identity: Identity,
+    data: web::Json<UpdateSelectedProfileSquadTagReq>,
+) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
 
-    let cloned_folder_path = config.rwr_profile_folder_path.clone();
+    let data: UpdateSelectedProfileSquadTagReq = data.into_inner();
 
-    HttpResponse::Ok()
+    if let Err(err) = check_squad_tag(&data.squad_tag) {
+        return HttpResponse::BadRequest()
+            .json(ResponseJson::default().set_err_msg(&err.to_string()));
+    }
+
+    let _lock_list = config
+        .profile_lock_registry
+        .lock_list(&data.profile_id_list)
+        .await;
+
+    return match update_profile_list_squad_tag_to_file(
+        &config.rwr_profile_folder_path,
+        &data.profile_id_list,
+        &data.squad_tag,
+        data.old_squad_tag.as_deref(),
+        &config.backup_store,
+    )
+    .await
+    {
+        Ok((res, change_list)) => {
+            audit_profile_list_change(
+                &config,
+                &identity,
+                "/profile/update_selected_profile_squad_tag",
+                &change_list,
+            )
+            .await;
+
+            HttpResponse::Ok().json(res)
+        }
+        Err(err) => {
+            error!("update selected profile squad tag error {:?}", err);
+            HttpResponse::BadRequest().json(
+                ResponseJson::default().set_err_msg("update selected profile squad tag error"),
+            )
+        }
+    };
 }
 
 #[instrument]
-#[post("/update")]
-async fn update_profile() -> impl Responder {
-    HttpResponse::Ok().body("update profile")
+#[post("/update/{id}")]
+async fn update_profile(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
+    data: web::Json<UpdateProfileReq>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite) {
+        return res;
+    }
+
+    let query_id = id.into_inner().0;
+    let _lock = config.profile_lock_registry.lock(query_id).await;
+
+    if let Err(res) = check_file_if_match(
+        format!("{}/{}.profile", &config.rwr_profile_folder_path, query_id),
+        if_match.as_deref(),
+    ) {
+        return res;
+    }
+    let source = extract_profile(query_id, &config.rwr_profile_folder_path);
+
+    return match source {
+        Ok(profile) => {
+            let new_profile = match data.into_inner().merge_into(&profile) {
+                Ok(new_profile) => new_profile,
+                Err(err) => {
+                    error!("merge profile error {:?}", err);
+                    return HttpResponse::BadRequest()
+                        .json(ResponseJson::default().set_err_msg(&err.to_string()));
+                }
+            };
+
+            info!("new_profile: {:?}", new_profile);
+
+            match save_profile_to_file_with_backup(
+                &config.rwr_profile_folder_path,
+                query_id,
+                &new_profile,
+                &config.backup_store,
+            ) {
+                Ok(_) => {
+                    audit_change(
+                        &config,
+                        &identity,
+                        "/profile/update",
+                        query_id,
+                        diff_profile(&profile, &new_profile),
+                    )
+                    .await;
+
+                    HttpResponse::Ok().json(
+                        ResponseJson::default().set_successful_msg("update profile successful"),
+                    )
+                }
+                Err(err) => {
+                    error!("save profile error {:?}", err);
+                    HttpResponse::BadRequest()
+                        .json(ResponseJson::default().set_err_msg("save profile error"))
+                }
+            }
+        }
+        Err(err) => {
+            error!("extract profile error {:?}", err);
+            err.error_response()
+        }
+    };
 }
 
 #[instrument]
 #[get("/download/{id}")]
-async fn download_profile(config: web::Data<AppData>, id: web::Path<(u64,)>) -> Result<NamedFile> {
+async fn download_profile(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> Result<NamedFile> {
     info!("");
+    check_profile_access(&identity, id.0)
+        .map_err(|res| InternalError::from_response("forbidden", res))?;
 
     let id: u64 = id.into_inner().0;
     let path = format!("{}/{}.profile", &config.rwr_profile_folder_path, id);
@@ -122,28 +329,52 @@ async fn download_profile(config: web::Data<AppData>, id: web::Path<(u64,)>) ->
 #[post("/upload/{id}")]
 async fn upload_profile(
     config: web::Data<AppData>,
+    identity: Identity,
     id: web::Path<(u64,)>,
+    if_match: Option<web::Header<IfMatch>>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, actix_web::Error> {
+    check_role_or_scope(&identity, Role::Moderator, ApiKeyScope::PersonWrite)
+        .map_err(|res| InternalError::from_response("forbidden", res))?;
+
     let id: u64 = id.into_inner().0;
 
     info!("in upload profile service, id: {}", id);
 
     let mut temp_file_name = Arc::new(Mutex::new(String::new()));
 
+    let temp_folder = match UploadTempFolder::create(&config.server_upload_temp_folder_path, id) {
+        Ok(temp_folder) => temp_folder,
+        Err(err) => {
+            error!("create {} profile upload temp folder error: {:?}", id, err);
+
+            let custom_err = ResponseJson::default().set_err_msg("create upload temp folder error");
+
+            return Ok(HttpResponse::BadRequest().json(custom_err));
+        }
+    };
+
     // iterate over multipart stream
     while let Some(mut field) = payload.try_next().await? {
         // A multipart/form-data stream has to contain `content_disposition`
         let content_disposition = field.content_disposition();
 
-        let filename = content_disposition.get_filename().unwrap();
-        // .map_or_else(|| "temp.person");
+        let filename = content_disposition.get_filename().unwrap_or_default();
+
+        if let Err(err) = check_upload_file_name(filename) {
+            error!("upload {} profile error: {:?}", id, err);
+
+            let custom_err = ResponseJson::default().set_err_msg(&err.to_string());
+
+            return Ok(HttpResponse::BadRequest().json(custom_err));
+        }
 
         let mut outer_file_name = Arc::clone(&temp_file_name);
         let mut outer_file_name = outer_file_name.lock().unwrap();
         *outer_file_name = String::from(filename);
 
-        let filepath = format!("{}/{}", &config.server_upload_temp_folder_path, &filename);
+        // 临时文件与目标文件均按 id 命名, 客户端文件名仅用于日志
+        let filepath = format!("{}/{}.profile", &temp_folder.path, id);
         info!("filepath: {}", filepath);
 
         // File::create is blocking operation, use threadpool
@@ -211,26 +442,74 @@ async fn upload_profile(
     //     }
     // }
 
-    let temp_file_name = temp_file_name.lock().unwrap();
+    let temp_file_name = temp_file_name.lock().unwrap().clone();
+
+    if temp_file_name.is_empty() {
+        error!("upload {} profile error: no file received", id);
+
+        let custom_err = ResponseJson::default().set_err_msg("no upload file received");
+
+        return Ok(HttpResponse::BadRequest().json(custom_err));
+    }
+
+    let _lock = config.profile_lock_registry.lock(id).await;
+
+    check_file_if_match(
+        format!("{}/{}.profile", &config.rwr_profile_folder_path, id),
+        if_match.as_deref(),
+    )
+    .map_err(|res| InternalError::from_response("precondition failed", res))?;
+
     info!("Ready to validate filename: {}", &temp_file_name);
 
-    let from_path = format!(
-        "{}/{}",
-        &config.server_upload_temp_folder_path, temp_file_name
-    );
-    let target_path = format!("{}/{}", &config.rwr_profile_folder_path, temp_file_name);
+    let profile = match extract_profile(id, &temp_folder.path) {
+        Ok(profile) => profile,
+        Err(err) => {
+            error!("extract {} profile error: {}", id, err);
+
+            return Ok(err.error_response());
+        }
+    };
 
-    return match std::fs::copy(from_path, target_path) {
-        Ok(_) => Ok(HttpResponse::Ok()
-            .json(ResponseJson::default().set_successful_msg("upload & replace profile success"))
-            .into()),
+    let from_path = format!("{}/{}.profile", &temp_folder.path, id);
+    let target_path = format!("{}/{}.profile", &config.rwr_profile_folder_path, id);
+
+    let source_profile = extract_profile(id, &config.rwr_profile_folder_path).unwrap_or_default();
+
+    if let Err(err) =
+        config
+            .backup_store
+            .backup(&config.rwr_profile_folder_path, id, BackupKind::Profile)
+    {
+        error!("backup {} profile error: {:?}", id, err);
+
+        let custom_err = ResponseJson::default().set_err_msg("backup profile error");
+
+        return Ok(HttpResponse::BadRequest().json(custom_err));
+    }
+
+    match copy_file_atomic(from_path, target_path) {
+        Ok(_) => {
+            audit_change(
+                &config,
+                &identity,
+                "/profile/upload",
+                id,
+                diff_profile(&source_profile, &profile),
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(
+                ResponseJson::default().set_successful_msg("upload & replace profile success"),
+            ))
+        }
         Err(err) => {
-            let err_msg = format!("extract {} person error: {}", id, err.to_string());
+            let err_msg = format!("extract {} profile error: {}", id, err.to_string());
             error!("{}", err);
 
             let custom_err = ResponseJson::default().set_err_msg(&err_msg);
 
             Ok(HttpResponse::BadRequest().json(custom_err))
         }
-    };
+    }
 }
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use serde::{Deserialize, Serialize};
+
+// 快照分级: 定时任务每小时生成 hourly, 并按间隔复制到 daily / weekly
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotTier {
+    Hourly,
+    Daily,
+    Weekly,
+    // 管理员手动触发
+    Manual,
+}
+
+impl SnapshotTier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SnapshotTier::Hourly => "hourly",
+            SnapshotTier::Daily => "daily",
+            SnapshotTier::Weekly => "weekly",
+            SnapshotTier::Manual => "manual",
+        }
+    }
+}
+
+pub static SNAPSHOT_TIER_LIST: [SnapshotTier; 4] = [
+    SnapshotTier::Hourly,
+    SnapshotTier::Daily,
+    SnapshotTier::Weekly,
+    SnapshotTier::Manual,
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotItem {
+    pub tier: SnapshotTier,
+    pub name: String,
+    pub created_at: i64,
+    pub size: u64,
+}
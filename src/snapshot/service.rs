@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::SnapshotTier;
+use super::utils::create_snapshot;
+use crate::auth::model::{Identity, Role};
+use crate::auth::utils::check_role;
+use crate::{model::ResponseJson, AppData};
+use actix_files::NamedFile;
+use actix_web::error::InternalError;
+use actix_web::{get, post, web, HttpResponse, Responder, Result};
+use tracing::{error, info, instrument};
+
+pub fn snapshot_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/snapshot")
+            .service(create_manual_snapshot)
+            .service(query_all_snapshot)
+            .service(download_snapshot),
+    );
+}
+
+#[instrument]
+#[post("/create")]
+async fn create_manual_snapshot(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    return match create_snapshot(&config, SnapshotTier::Manual).await {
+        Ok(item) => {
+            info!(
+                "admin: {} create snapshot: {} successful",
+                identity.name, item.name
+            );
+            HttpResponse::Ok().json(item)
+        }
+        Err(err) => {
+            error!("create snapshot error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("create snapshot error"))
+        }
+    };
+}
+
+#[instrument]
+#[get("/query_all")]
+async fn query_all_snapshot(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    return match config.snapshot_store.query_all() {
+        Ok(snapshot_list) => HttpResponse::Ok().json(snapshot_list),
+        Err(err) => {
+            error!("query all snapshot error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("query all snapshot error"))
+        }
+    };
+}
+
+#[instrument]
+#[get("/download/{tier}/{name}")]
+async fn download_snapshot(
+    config: web::Data<AppData>,
+    identity: Identity,
+    path: web::Path<(SnapshotTier, String)>,
+) -> Result<NamedFile> {
+    info!("");
+    check_role(&identity, Role::Admin)
+        .map_err(|res| InternalError::from_response("forbidden", res))?;
+
+    let (tier, name) = path.into_inner();
+
+    let file_path = config
+        .snapshot_store
+        .get_file_path(tier, &name)
+        .map_err(|err| {
+            error!("download snapshot error: {:?}", err);
+            InternalError::from_response(
+                "snapshot not found",
+                HttpResponse::NotFound()
+                    .json(ResponseJson::default().set_err_msg("snapshot not found")),
+            )
+        })?;
+
+    Ok(NamedFile::open_async(file_path).await?)
+}
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{SnapshotItem, SnapshotTier, SNAPSHOT_TIER_LIST};
+use crate::constant::{MAX_MANUAL_SNAPSHOT_COUNT, SNAPSHOT_FOLDER_NAME};
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+// 存档目录整体打包的 tar.gz 快照
+// 目录结构: {server_data_folder_path}/snapshots/{tier}/{yyyyMMddHHmmss}.tar.gz
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    folder_path: PathBuf,
+    hourly_count: usize,
+    daily_count: usize,
+    weekly_count: usize,
+}
+
+fn get_modified(file_path: &Path) -> Result<DateTime<Utc>> {
+    Ok(fs::metadata(file_path)?.modified()?.into())
+}
+
+impl SnapshotStore {
+    pub fn new(
+        data_path: &str,
+        hourly_count: usize,
+        daily_count: usize,
+        weekly_count: usize,
+    ) -> Self {
+        Self {
+            folder_path: Path::new(data_path).join(SNAPSHOT_FOLDER_NAME),
+            hourly_count,
+            daily_count,
+            weekly_count,
+        }
+    }
+
+    fn get_tier_folder(&self, tier: SnapshotTier) -> PathBuf {
+        self.folder_path.join(tier.name())
+    }
+
+    fn get_max_count(&self, tier: SnapshotTier) -> usize {
+        match tier {
+            SnapshotTier::Hourly => self.hourly_count,
+            SnapshotTier::Daily => self.daily_count,
+            SnapshotTier::Weekly => self.weekly_count,
+            SnapshotTier::Manual => MAX_MANUAL_SNAPSHOT_COUNT,
+        }
+    }
+
+    // 按时间倒序返回该级别的全部快照
+    pub fn query(&self, tier: SnapshotTier) -> Result<Vec<SnapshotItem>> {
+        let tier_folder = self.get_tier_folder(tier);
+
+        if !tier_folder.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut snapshot_list = vec![];
+
+        for entry in fs::read_dir(tier_folder)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if !name.ends_with(".tar.gz") {
+                continue;
+            }
+
+            let modified = get_modified(&entry.path())?;
+
+            snapshot_list.push((
+                modified,
+                SnapshotItem {
+                    tier,
+                    name,
+                    created_at: modified.timestamp(),
+                    size: entry.metadata()?.len(),
+                },
+            ));
+        }
+
+        // 同一秒内可能生成多份, 按完整精度的修改时间排序
+        snapshot_list.sort_by_key(|(modified, _)| Reverse(*modified));
+
+        Ok(snapshot_list.into_iter().map(|(_, item)| item).collect())
+    }
+
+    pub fn query_all(&self) -> Result<Vec<SnapshotItem>> {
+        let mut snapshot_list = vec![];
+
+        for tier in SNAPSHOT_TIER_LIST {
+            snapshot_list.append(&mut self.query(tier)?);
+        }
+
+        Ok(snapshot_list)
+    }
+
+    // 仅允许访问已存在的快照, 避免路径穿越
+    pub fn get_file_path(&self, tier: SnapshotTier, name: &str) -> Result<PathBuf> {
+        self.query(tier)?
+            .into_iter()
+            .find(|item| item.name == name)
+            .map(|item| self.get_tier_folder(tier).join(item.name))
+            .ok_or(anyhow!("snapshot not found: {}/{}", tier.name(), name))
+    }
+
+    // 打包整个存档目录, 调用方需持有存档目录锁
+    pub fn create(&self, profile_folder_path: &str, tier: SnapshotTier) -> Result<SnapshotItem> {
+        let tier_folder = self.get_tier_folder(tier);
+        fs::create_dir_all(&tier_folder)?;
+
+        let time_str = Utc::now().format("%Y%m%d%H%M%S").to_string();
+        let mut name = format!("{}.tar.gz", time_str);
+        let mut index = 1;
+        while tier_folder.join(&name).exists() {
+            name = format!("{}-{}.tar.gz", time_str, index);
+            index += 1;
+        }
+
+        let file_path = tier_folder.join(&name);
+        let temp_path = tier_folder.join(format!(".{}.tmp", name));
+
+        let file = fs::File::create(&temp_path)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+        let res = builder
+            .append_dir_all("profiles", profile_folder_path)
+            .and_then(|_| builder.into_inner())
+            .and_then(|encoder| encoder.finish())
+            .and_then(|file| file.sync_all());
+
+        if let Err(err) = res {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err.into());
+        }
+
+        fs::rename(&temp_path, &file_path)?;
+
+        info!("snapshot {} created: {:?}", tier.name(), file_path);
+
+        self.prune(tier)?;
+
+        Ok(SnapshotItem {
+            tier,
+            created_at: get_modified(&file_path)?.timestamp(),
+            size: fs::metadata(&file_path)?.len(),
+            name,
+        })
+    }
+
+    // 距离上一次 daily / weekly 快照已满间隔时, 复制本次 hourly 快照
+    pub fn promote(&self, item: &SnapshotItem) -> Result<()> {
+        let source_path = self.get_tier_folder(item.tier).join(&item.name);
+
+        for (tier, interval_secs) in [
+            (SnapshotTier::Daily, 60 * 60 * 24),
+            (SnapshotTier::Weekly, 60 * 60 * 24 * 7),
+        ] {
+            // 预留几分钟误差, 避免定时任务的抖动导致跳过一次
+            let is_due = match self.query(tier)?.first() {
+                Some(latest) => item.created_at - latest.created_at >= interval_secs - 60 * 5,
+                None => true,
+            };
+
+            if !is_due {
+                continue;
+            }
+
+            let tier_folder = self.get_tier_folder(tier);
+            fs::create_dir_all(&tier_folder)?;
+
+            fs::copy(&source_path, tier_folder.join(&item.name))?;
+
+            info!("snapshot {} promoted to {}", item.name, tier.name());
+
+            self.prune(tier)?;
+        }
+
+        Ok(())
+    }
+
+    // 0 表示不限制
+    fn prune(&self, tier: SnapshotTier) -> Result<()> {
+        let max_count = self.get_max_count(tier);
+
+        if max_count == 0 {
+            return Ok(());
+        }
+
+        for item in self.query(tier)?.into_iter().skip(max_count) {
+            fs::remove_file(self.get_tier_folder(tier).join(&item.name))?;
+
+            info!("snapshot {}/{} removed", tier.name(), item.name);
+        }
+
+        Ok(())
+    }
+}
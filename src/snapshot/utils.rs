@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{SnapshotItem, SnapshotTier};
+use crate::AppData;
+use anyhow::Result;
+
+// 打包期间持有存档目录锁, 保证快照中不会出现写了一半的存档
+pub async fn create_snapshot(config: &AppData, tier: SnapshotTier) -> Result<SnapshotItem> {
+    let _folder_lock = config.profile_lock_registry.lock_folder().await;
+
+    let snapshot_store = config.snapshot_store.clone();
+    let folder_path = config.rwr_profile_folder_path.clone();
+
+    tokio::task::spawn_blocking(move || snapshot_store.create(&folder_path, tier)).await?
+}
+
+pub async fn create_scheduled_snapshot(config: &AppData) -> Result<SnapshotItem> {
+    let item = create_snapshot(config, SnapshotTier::Hourly).await?;
+
+    let snapshot_store = config.snapshot_store.clone();
+    let promote_item = item.clone();
+
+    tokio::task::spawn_blocking(move || snapshot_store.promote(&promote_item)).await??;
+
+    Ok(item)
+}
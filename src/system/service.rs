@@ -1,4 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
+use crate::audit::model::AuditQuery;
+use crate::auth::model::{ApiKeyScope, Identity, Role};
+use crate::auth::utils::{check_role, check_role_or_scope};
 use crate::system::extract::get_ranks_data;
 use crate::system::model::RankItem;
 use crate::AppData;
@@ -15,7 +18,8 @@ pub fn system_config(cfg: &mut web::ServiceConfig) {
         web::scope("/system")
             .service(query_quick_items)
             .service(update_quick_items)
-            .service(query_ranks),
+            .service(query_ranks)
+            .service(query_audit),
     );
 }
 
@@ -38,9 +42,13 @@ async fn query_quick_items(config: web::Data<AppData>) -> impl Responder {
 #[post("/update_quick_items")]
 async fn update_quick_items(
     config: web::Data<AppData>,
+    identity: Identity,
     data: web::Json<Vec<QuickItem>>,
 ) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Admin, ApiKeyScope::SystemWrite) {
+        return res;
+    }
 
     let quick_items: Vec<QuickItem> = data.into_inner();
 
@@ -60,8 +68,11 @@ async fn update_quick_items(
 
 #[instrument]
 #[get("/query_ranks")]
-async fn query_ranks(config: web::Data<AppData>) -> impl Responder {
+async fn query_ranks(config: web::Data<AppData>, identity: Identity) -> impl Responder {
     info!("");
+    if let Err(res) = check_role_or_scope(&identity, Role::Player, ApiKeyScope::CacheRead) {
+        return res;
+    }
 
     let mut snapshot_ranks = config.snapshot_ranks.lock().await;
 
@@ -84,3 +95,25 @@ async fn query_ranks(config: web::Data<AppData>) -> impl Responder {
 
     return HttpResponse::Ok().json(snapshot_ranks_data);
 }
+
+#[instrument]
+#[get("/audit")]
+async fn query_audit(
+    config: web::Data<AppData>,
+    identity: Identity,
+    query: web::Query<AuditQuery>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    return match config.audit_log.query(&query).await {
+        Ok(record_list) => HttpResponse::Ok().json(record_list),
+        Err(err) => {
+            error!("query audit error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("query audit error"))
+        }
+    };
+}
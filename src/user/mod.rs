@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
 mod extract;
-mod model;
+pub mod model;
 pub mod service;
-mod utils;
+pub mod store;
+pub mod throttle;
+pub mod utils;
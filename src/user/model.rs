@@ -14,6 +14,66 @@ pub struct RegisterReq {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterConfirmReq {
+    pub challenge_id: String,
+}
+
+// 注册验证: 玩家需在游戏内将 squad_tag 设置为 code 后确认注册
+#[derive(Debug, Clone)]
+pub struct RegisterChallenge {
+    pub challenge_id: String,
+    pub username: String,
+    pub profile_id: u64,
+    pub password_hash: String,
+    pub code: String,
+    pub expire_at: i64,
+    // 确认注册时存档标签与验证码不一致的次数
+    pub failed_count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterChallengeRes {
+    pub challenge_id: String,
+    pub code: String,
+    // 验证码过期时间戳(秒)
+    pub expire_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateUserReq {
+    pub username: String,
+    pub password: String,
+    pub user_id: u64,
+    pub admin: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameUserReq {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateUserAdminReq {
+    pub admin: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetPasswordReq {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordReq {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccountReq {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub name: String,
     pub user_id: u64,
@@ -21,7 +81,24 @@ pub struct User {
     pub admin: u8,
 }
 
+// 返回给前端的用户信息, 不包含密码
 #[derive(Debug, Serialize, Deserialize)]
+pub struct UserRes {
+    pub name: String,
+    pub user_id: u64,
+    pub admin: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginRes {
+    #[serde(flatten)]
+    pub user: UserRes,
+    pub token: String,
+    // token 过期时间戳(秒)
+    pub expire_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Users {
     pub user_list: Vec<User>,
 }
@@ -36,3 +113,13 @@ impl Default for User {
         }
     }
 }
+
+impl From<&User> for UserRes {
+    fn from(user: &User) -> Self {
+        Self {
+            name: user.name.clone(),
+            user_id: user.user_id,
+            admin: user.admin,
+        }
+    }
+}
@@ -1,70 +1,95 @@
 // SPDX-License-Identifier: GPL-3.0-only
+use crate::auth::model::{Identity, Role};
+use crate::auth::utils::{check_role, issue_token, revoke_session};
+use crate::constant::REGISTER_MAX_FAILURES;
 use crate::model::ResponseJson;
-use crate::user::model::{LoginReq, RegisterReq, User};
+use crate::user::extract::get_user_profile_id;
+use crate::user::model::{
+    ChangePasswordReq, CreateUserReq, DeleteAccountReq, LoginReq, LoginRes, RegisterChallengeRes,
+    RegisterConfirmReq, RegisterReq, RenameUserReq, ResetPasswordReq, UpdateUserAdminReq, User,
+    UserRes,
+};
+use crate::user::throttle::{ip_key, username_key};
 use crate::user::utils::{
-    get_user_info, get_user_json_data, register_user, update_user_list, validate_user,
+    check_admin_value, check_register_challenge, create_user, delete_user, get_user_by_id,
+    get_user_info, get_user_json_data, hash_password, modify_user, new_register_challenge,
+    register_user, validate_user, verify_password,
 };
 use crate::AppData;
-use actix_web::{get, post, web, HttpResponse, Responder};
-use tracing::{error, info, instrument};
+use actix_web::http::header;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use anyhow::anyhow;
+use chrono::prelude::*;
+use tracing::{error, info, instrument, warn};
 
 pub fn user_config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/user")
             .service(register)
+            .service(register_confirm)
             .service(login)
+            .service(logout)
+            .service(refresh)
+            .service(change_password)
+            .service(delete_account)
             .service(get_user)
-            .service(get_all_user),
+            .service(get_all_user)
+            .service(admin_create_user)
+            .service(admin_delete_user)
+            .service(admin_rename_user)
+            .service(admin_update_user_admin)
+            .service(admin_reset_password),
     );
 }
 
-#[instrument]
+#[instrument(skip(user))]
 #[post("/register")]
 async fn register(config: web::Data<AppData>, user: web::Json<RegisterReq>) -> impl Responder {
     info!("");
-    config.user_json_lock.lock().await;
 
-    let res = register_user(&user.username, &user.password, &config);
-    info!("register_user successful, id:{:?}", res);
+    // 扫描存档与计算密码哈希较慢, 在获取 UserStore 锁之前完成, 避免阻塞登录
+    let res = get_user_profile_id(&user.username, &config.rwr_profile_folder_path).and_then(|id| {
+        let password_hash = hash_password(&user.password)?;
+
+        new_register_challenge(
+            &user.username,
+            id,
+            password_hash,
+            &config.rwr_profile_folder_path,
+        )
+    });
+
+    let res = match res {
+        Ok(challenge) => {
+            let mut store = config.user_store.lock().await;
+
+            register_user(&challenge.username, challenge.profile_id, &mut store).map(|_| challenge)
+        }
+        Err(err) => Err(err),
+    };
 
     return match res {
-        Ok(id) => {
-            info!("match res successful");
-            return match get_user_json_data(&config.server_data_folder_path) {
-                Ok(mut user_json_data) => {
-                    user_json_data.user_list.push(User {
-                        name: user.username.clone(),
-                        password: user.password.clone(),
-                        user_id: id,
-                        admin: 0,
-                    });
-
-                    match update_user_list(
-                        user_json_data.user_list,
-                        &config.server_data_folder_path,
-                    ) {
-                        Ok(_) => {
-                            info!(
-                                "user: {}, profile_id: {} register successful",
-                                user.username, id
-                            );
-                            HttpResponse::Ok().json(
-                                ResponseJson::default().set_successful_msg("register successful"),
-                            )
-                        }
-                        Err(e) => {
-                            error!("update_user_list error: {:?}", e);
-                            HttpResponse::BadRequest()
-                                .json(ResponseJson::default().set_err_msg(&e.to_string()))
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("get_user_json_data error: {:?}", e);
-                    HttpResponse::BadRequest()
-                        .json(ResponseJson::default().set_err_msg(&e.to_string()))
-                }
+        Ok(challenge) => {
+            info!(
+                "user: {}, profile_id: {} register challenge created",
+                user.username, challenge.profile_id
+            );
+
+            let res = RegisterChallengeRes {
+                challenge_id: challenge.challenge_id.clone(),
+                code: challenge.code.clone(),
+                expire_at: challenge.expire_at,
             };
+
+            let mut register_challenge_map = config.register_challenge_map.lock().await;
+
+            // 每个存档仅保留最新的一个验证码
+            let now = Utc::now().timestamp();
+            register_challenge_map
+                .retain(|_, item| item.expire_at >= now && item.profile_id != challenge.profile_id);
+            register_challenge_map.insert(challenge.challenge_id.clone(), challenge);
+
+            HttpResponse::Ok().json(res)
         }
         Err(err) => {
             error!("register, error: {:?}", err);
@@ -74,27 +99,262 @@ async fn register(config: web::Data<AppData>, user: web::Json<RegisterReq>) -> i
 }
 
 #[instrument]
-#[post("/login")]
-async fn login(config: web::Data<AppData>, info: web::Json<LoginReq>) -> impl Responder {
-    config.user_json_lock.lock().await;
+#[post("/register_confirm")]
+async fn register_confirm(
+    config: web::Data<AppData>,
+    data: web::Json<RegisterConfirmReq>,
+) -> impl Responder {
+    info!("");
+
+    let mut challenge = {
+        let mut register_challenge_map = config.register_challenge_map.lock().await;
+
+        let now = Utc::now().timestamp();
+        register_challenge_map.retain(|_, item| item.expire_at >= now);
+
+        match register_challenge_map.remove(&data.challenge_id) {
+            Some(challenge) if challenge.expire_at >= Utc::now().timestamp() => challenge,
+            _ => {
+                return HttpResponse::BadRequest().json(
+                    ResponseJson::default().set_err_msg("register challenge not found or expired"),
+                );
+            }
+        }
+    };
 
-    match validate_user(
-        &info.username,
-        &info.password,
-        &config.server_data_folder_path,
+    match check_register_challenge(&challenge, &config.rwr_profile_folder_path) {
+        Ok(true) => {}
+        Ok(false) => {
+            info!(
+                "user: {}, profile_id: {} squad_tag not match register code",
+                challenge.username, challenge.profile_id
+            );
+
+            // 游戏内尚未保存标签时允许使用同一验证码重试, 超过次数后作废
+            challenge.failed_count += 1;
+
+            if challenge.failed_count < REGISTER_MAX_FAILURES {
+                let mut register_challenge_map = config.register_challenge_map.lock().await;
+                register_challenge_map.insert(challenge.challenge_id.clone(), challenge);
+            }
+
+            return HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("squad_tag not match register code"));
+        }
+        Err(e) => {
+            error!("check register challenge error: {:?}", e);
+            return HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg(&e.to_string()));
+        }
+    }
+
+    let mut store = config.user_store.lock().await;
+
+    match create_user(
+        User {
+            name: challenge.username.clone(),
+            password: challenge.password_hash.clone(),
+            user_id: challenge.profile_id,
+            admin: 0,
+        },
+        &mut store,
     ) {
+        Ok(()) => {
+            info!(
+                "user: {}, profile_id: {} register successful",
+                challenge.username, challenge.profile_id
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("register successful"))
+        }
+        Err(e) => {
+            error!("register confirm error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument(skip(req, info))]
+#[post("/login")]
+async fn login(
+    config: web::Data<AppData>,
+    req: HttpRequest,
+    info: web::Json<LoginReq>,
+) -> impl Responder {
+    let peer_ip = req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+    let throttle_key_list = [ip_key(&peer_ip), username_key(&info.username)];
+
+    if let Some(wait_secs) = config.login_throttle.check(&throttle_key_list).await {
+        warn!(
+            "login throttled, user: {}, peer: {}, retry after: {}s",
+            info.username, peer_ip, wait_secs
+        );
+        return HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, wait_secs.to_string()))
+            .json(ResponseJson::default().set_too_many_requests_msg(&format!(
+                "too many login attempts, retry after {}s",
+                wait_secs
+            )));
+    }
+
+    let mut store = config.user_store.lock().await;
+
+    match validate_user(&info.username, &info.password, &mut store) {
         Ok(_) => {
-            return match get_user_info(&info.username, &config.server_data_folder_path) {
-                Ok(res) => HttpResponse::Ok().json(res),
+            config
+                .login_throttle
+                .record_success(&username_key(&info.username))
+                .await;
+
+            return match get_user_info(&info.username, &mut store).and_then(|user| {
+                let (token, claims) = issue_token(&config, &user)?;
+
+                Ok(LoginRes {
+                    user: UserRes::from(&user),
+                    token,
+                    expire_at: claims.exp,
+                })
+            }) {
+                Ok(res) => {
+                    info!(
+                        "user: {} login successful, peer: {}",
+                        res.user.name, peer_ip
+                    );
+                    HttpResponse::Ok().json(res)
+                }
                 Err(e) => {
                     error!("{:?}", e);
                     HttpResponse::BadRequest()
                         .json(ResponseJson::default().set_err_msg(&e.to_string()))
                 }
-            }
+            };
+        }
+        Err(e) => {
+            let failures = config
+                .login_throttle
+                .record_failure(&throttle_key_list)
+                .await;
+
+            warn!(
+                "login failed, user: {}, peer: {}, failures: {}, error: {:?}",
+                info.username, peer_ip, failures, e
+            );
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument]
+#[post("/logout")]
+async fn logout(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    revoke_session(&config, &identity).await;
+
+    info!("user: {} logout successful", identity.name);
+    HttpResponse::Ok().json(ResponseJson::default().set_successful_msg("logout successful"))
+}
+
+#[instrument]
+#[post("/refresh")]
+async fn refresh(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    let mut store = config.user_store.lock().await;
+
+    // 重新读取用户信息, 使权限变更或账号删除在刷新时生效
+    return match get_user_by_id(identity.user_id, &mut store).and_then(|user| {
+        let (token, claims) = issue_token(&config, &user)?;
+
+        Ok(LoginRes {
+            user: UserRes::from(&user),
+            token,
+            expire_at: claims.exp,
+        })
+    }) {
+        Ok(res) => {
+            // 旧 token 立即失效
+            revoke_session(&config, &identity).await;
+
+            info!("user: {} refresh token successful", res.user.name);
+            HttpResponse::Ok().json(res)
+        }
+        Err(e) => {
+            error!("refresh token error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    };
+}
+
+#[instrument(skip(data))]
+#[post("/change_password")]
+async fn change_password(
+    config: web::Data<AppData>,
+    identity: Identity,
+    data: web::Json<ChangePasswordReq>,
+) -> impl Responder {
+    info!("");
+    let data = data.into_inner();
+
+    let mut store = config.user_store.lock().await;
+
+    let res = modify_user(identity.user_id, &mut store, |user, _| {
+        if !verify_password(&data.old_password, &user.password) {
+            return Err(anyhow!("old password not correct"));
+        }
+
+        user.password = hash_password(&data.new_password)?;
+
+        Ok(())
+    });
+
+    match res {
+        Ok(()) => {
+            // 修改密码后该用户的全部会话失效, 需重新登录
+            revoke_session(&config, &identity).await;
+
+            info!("user: {} change password successful", identity.name);
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("change password successful"))
+        }
+        Err(e) => {
+            error!("user: {} change password error: {:?}", identity.name, e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument(skip(data))]
+#[post("/delete_account")]
+async fn delete_account(
+    config: web::Data<AppData>,
+    identity: Identity,
+    data: web::Json<DeleteAccountReq>,
+) -> impl Responder {
+    info!("");
+
+    let mut store = config.user_store.lock().await;
+
+    let res = get_user_by_id(identity.user_id, &mut store).and_then(|user| {
+        if !verify_password(&data.password, &user.password) {
+            return Err(anyhow!("password not correct"));
+        }
+
+        delete_user(identity.user_id, &mut store)
+    });
+
+    match res {
+        Ok(user) => {
+            revoke_session(&config, &identity).await;
+
+            info!(
+                "user: {}, user_id: {} delete account successful",
+                user.name, user.user_id
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("delete account successful"))
         }
         Err(e) => {
-            error!("{:?}", e);
+            error!("user: {} delete account error: {:?}", identity.name, e);
             HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
         }
     }
@@ -102,12 +362,255 @@ async fn login(config: web::Data<AppData>, info: web::Json<LoginReq>) -> impl Re
 
 #[instrument]
 #[get("/query/{id}")]
-async fn get_user(id: web::Path<(u64,)>) -> impl Responder {
-    HttpResponse::Ok().json(ResponseJson::default())
+async fn get_user(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> impl Responder {
+    info!("");
+    let user_id = id.into_inner().0;
+
+    if identity.user_id != user_id {
+        if let Err(res) = check_role(&identity, Role::Admin) {
+            return res;
+        }
+    }
+
+    let mut store = config.user_store.lock().await;
+
+    match get_user_by_id(user_id, &mut store) {
+        Ok(user) => HttpResponse::Ok().json(UserRes::from(&user)),
+        Err(e) => {
+            error!("get user error: {:?}", e);
+            HttpResponse::NotFound().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
 }
 
 #[instrument]
 #[get("/query_all")]
-async fn get_all_user(config: web::Data<AppData>) -> impl Responder {
-    HttpResponse::Ok().json(ResponseJson::default())
+async fn get_all_user(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let mut store = config.user_store.lock().await;
+
+    match get_user_json_data(&mut store) {
+        Ok(user_json) => HttpResponse::Ok().json(
+            user_json
+                .user_list
+                .iter()
+                .map(UserRes::from)
+                .collect::<Vec<UserRes>>(),
+        ),
+        Err(e) => {
+            error!("get all user error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument(skip(data))]
+#[post("/create")]
+async fn admin_create_user(
+    config: web::Data<AppData>,
+    identity: Identity,
+    data: web::Json<CreateUserReq>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let data = data.into_inner();
+
+    let mut store = config.user_store.lock().await;
+
+    let res = hash_password(&data.password).and_then(|password_hash| {
+        create_user(
+            User {
+                name: data.username.clone(),
+                user_id: data.user_id,
+                password: password_hash,
+                admin: data.admin,
+            },
+            &mut store,
+        )
+    });
+
+    match res {
+        Ok(()) => {
+            info!(
+                "admin: {} create user: {}, user_id: {} successful",
+                identity.name, data.username, data.user_id
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("create user successful"))
+        }
+        Err(e) => {
+            error!("create user error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument]
+#[post("/delete/{id}")]
+async fn admin_delete_user(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let user_id = id.into_inner().0;
+
+    let mut store = config.user_store.lock().await;
+
+    match delete_user(user_id, &mut store) {
+        Ok(user) => {
+            info!(
+                "admin: {} delete user: {}, user_id: {} successful",
+                identity.name, user.name, user_id
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("delete user successful"))
+        }
+        Err(e) => {
+            error!("delete user error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument]
+#[post("/rename/{id}")]
+async fn admin_rename_user(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+    data: web::Json<RenameUserReq>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let user_id = id.into_inner().0;
+    let username = data.into_inner().username;
+
+    let mut store = config.user_store.lock().await;
+
+    let res = modify_user(user_id, &mut store, |user, others| {
+        if others.iter().any(|x| x.name == username) {
+            return Err(anyhow!("user already exists"));
+        }
+
+        user.name = username.clone();
+
+        Ok(())
+    });
+
+    match res {
+        Ok(()) => {
+            info!(
+                "admin: {} rename user_id: {} to {} successful",
+                identity.name, user_id, username
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("rename user successful"))
+        }
+        Err(e) => {
+            error!("rename user error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument]
+#[post("/update_admin/{id}")]
+async fn admin_update_user_admin(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+    data: web::Json<UpdateUserAdminReq>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let user_id = id.into_inner().0;
+    let admin = data.into_inner().admin;
+
+    let mut store = config.user_store.lock().await;
+
+    let res = modify_user(user_id, &mut store, |user, _| {
+        check_admin_value(admin)?;
+
+        user.admin = admin;
+
+        Ok(())
+    });
+
+    match res {
+        Ok(()) => {
+            info!(
+                "admin: {} update user_id: {} admin to {} successful",
+                identity.name, user_id, admin
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("update user admin successful"))
+        }
+        Err(e) => {
+            error!("update user admin error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
+}
+
+#[instrument(skip(data))]
+#[post("/reset_password/{id}")]
+async fn admin_reset_password(
+    config: web::Data<AppData>,
+    identity: Identity,
+    id: web::Path<(u64,)>,
+    data: web::Json<ResetPasswordReq>,
+) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let user_id = id.into_inner().0;
+
+    let mut store = config.user_store.lock().await;
+
+    let res = hash_password(&data.password).and_then(|password_hash| {
+        modify_user(user_id, &mut store, |user, _| {
+            user.password = password_hash;
+
+            Ok(())
+        })
+    });
+
+    match res {
+        Ok(()) => {
+            info!(
+                "admin: {} reset password of user_id: {} successful",
+                identity.name, user_id
+            );
+            HttpResponse::Ok()
+                .json(ResponseJson::default().set_successful_msg("reset password successful"))
+        }
+        Err(e) => {
+            error!("reset password error: {:?}", e);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&e.to_string()))
+        }
+    }
 }
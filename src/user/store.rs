@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::Users;
+use crate::constant::USERS_JSON_FILE_NAME;
+use crate::utils::write_file_atomic;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::info;
+
+#[derive(Debug, Default)]
+pub struct UserCache {
+    users: Option<Users>,
+    // 缓存对应的文件修改时间与大小, 不一致时重新读取
+    modified: Option<(SystemTime, u64)>,
+}
+
+// users.json 的唯一读写入口, 所有读写需先通过 lock 获取 UserStoreGuard
+#[derive(Debug)]
+pub struct UserStore {
+    file_path: PathBuf,
+    cache: Mutex<UserCache>,
+}
+
+pub struct UserStoreGuard<'a> {
+    file_path: &'a Path,
+    cache: MutexGuard<'a, UserCache>,
+}
+
+fn get_file_modified(file_path: &Path) -> Result<(SystemTime, u64)> {
+    let metadata = fs::metadata(file_path)?;
+
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+impl UserStore {
+    pub fn new(data_path: &str) -> Self {
+        Self {
+            file_path: Path::new(data_path).join(USERS_JSON_FILE_NAME),
+            cache: Mutex::new(UserCache::default()),
+        }
+    }
+
+    pub async fn lock(&self) -> UserStoreGuard<'_> {
+        UserStoreGuard {
+            file_path: &self.file_path,
+            cache: self.cache.lock().await,
+        }
+    }
+}
+
+impl UserStoreGuard<'_> {
+    pub fn load(&mut self) -> Result<Users> {
+        let modified = get_file_modified(self.file_path)?;
+
+        if let (Some(users), Some(cached_modified)) = (&self.cache.users, self.cache.modified) {
+            if cached_modified == modified {
+                return Ok(users.clone());
+            }
+        }
+
+        info!("reload user store: {:?}", self.file_path);
+
+        let file = fs::read_to_string(self.file_path)?;
+        let users: Users = serde_json::from_str(file.trim())?;
+
+        self.cache.users = Some(users.clone());
+        self.cache.modified = Some(modified);
+
+        Ok(users)
+    }
+
+    pub fn save(&mut self, users: Users) -> Result<()> {
+        let json_str = serde_json::to_string(&users)?;
+
+        write_file_atomic(self.file_path, json_str.as_bytes())?;
+
+        self.cache.modified = Some(get_file_modified(self.file_path)?);
+        self.cache.users = Some(users);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use chrono::prelude::*;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Default, Clone)]
+struct FailureRecord {
+    failures: u32,
+    last_failure_at: i64,
+    blocked_until: i64,
+}
+
+// 登录失败限流: 以 ip 与用户名分别计数, 每次失败后等待时间指数增长, 达到上限后锁定
+#[derive(Debug)]
+pub struct LoginThrottle {
+    max_failures: u32,
+    backoff_base_secs: u64,
+    lockout_secs: u64,
+    record_map: Mutex<HashMap<String, FailureRecord>>,
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+pub fn username_key(username: &str) -> String {
+    format!("user:{}", username)
+}
+
+impl LoginThrottle {
+    pub fn new(max_failures: u32, backoff_base_secs: u64, lockout_secs: u64) -> Self {
+        Self {
+            max_failures,
+            backoff_base_secs,
+            lockout_secs,
+            record_map: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // 返回需要等待的秒数, None 表示允许尝试
+    pub async fn check(&self, key_list: &[String]) -> Option<i64> {
+        let record_map = self.record_map.lock().await;
+        let now = Utc::now().timestamp();
+
+        key_list
+            .iter()
+            .filter_map(|key| record_map.get(key))
+            .map(|record| record.blocked_until - now)
+            .filter(|wait_secs| *wait_secs > 0)
+            .max()
+    }
+
+    // 记录失败, 返回各 key 当前连续失败次数的最大值
+    pub async fn record_failure(&self, key_list: &[String]) -> u32 {
+        let mut record_map = self.record_map.lock().await;
+        let now = Utc::now().timestamp();
+
+        // 清理已过锁定期且长时间无失败的记录
+        let lockout_secs = self.lockout_secs as i64;
+        record_map.retain(|_, record| {
+            record.blocked_until > now || now - record.last_failure_at < lockout_secs
+        });
+
+        let mut max_failures = 0;
+
+        for key in key_list {
+            let record = record_map.entry(key.clone()).or_default();
+
+            record.failures += 1;
+            record.last_failure_at = now;
+
+            let wait_secs = if record.failures >= self.max_failures {
+                self.lockout_secs
+            } else {
+                let backoff = self
+                    .backoff_base_secs
+                    .saturating_mul(1 << (record.failures - 1).min(32));
+                backoff.min(self.lockout_secs)
+            };
+            record.blocked_until = now + wait_secs as i64;
+
+            max_failures = max_failures.max(record.failures);
+        }
+
+        max_failures
+    }
+
+    pub async fn record_success(&self, key: &str) {
+        let mut record_map = self.record_map.lock().await;
+
+        record_map.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ip_key, username_key, LoginThrottle};
+
+    // 等待秒数按当前时间计算, 跨秒时可能少 1
+    fn assert_wait(wait_secs: Option<i64>, expected: i64) {
+        let wait_secs = wait_secs.unwrap();
+
+        assert!(
+            wait_secs == expected || wait_secs == expected - 1,
+            "wait: {}, expected: {}",
+            wait_secs,
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn backoff_and_lockout() {
+        let throttle = LoginThrottle::new(3, 10, 600);
+        let key_list = [ip_key("127.0.0.1"), username_key("ALICE")];
+
+        assert_eq!(throttle.check(&key_list).await, None);
+
+        assert_eq!(throttle.record_failure(&key_list).await, 1);
+        assert_wait(throttle.check(&key_list).await, 10);
+
+        assert_eq!(throttle.record_failure(&key_list).await, 2);
+        assert_wait(throttle.check(&key_list).await, 20);
+
+        // 达到 login_max_failures 后锁定
+        assert_eq!(throttle.record_failure(&key_list).await, 3);
+        assert_wait(throttle.check(&key_list).await, 600);
+
+        // 登录成功只清除用户名计数, 同一 ip 仍处于锁定
+        throttle.record_success(&username_key("ALICE")).await;
+        assert_eq!(throttle.check(&[username_key("ALICE")]).await, None);
+        assert_wait(throttle.check(&key_list).await, 600);
+    }
+}
@@ -1,58 +1,74 @@
 // SPDX-License-Identifier: GPL-3.0-only
-use super::model::{User, Users};
-use crate::constant::USERS_JSON_FILE_NAME;
-use crate::user::extract::get_user_profile_id;
-use crate::AppData;
+use super::model::{RegisterChallenge, User, Users};
+use super::store::UserStoreGuard;
+use crate::constant::{REGISTER_CHALLENGE_EXPIRE_SECS, REGISTER_CODE_LEN};
+use crate::profile::extract::extract_profile;
 use anyhow::{anyhow, Result};
-use serde_json;
-use std::fs;
-use std::io::Write;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::prelude::*;
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore};
 use tracing_log::log::info;
 
-pub fn get_user_json_data(data_path: &str) -> Result<Users> {
-    let file_name = format!("{}/{}", data_path, USERS_JSON_FILE_NAME);
-    let file = fs::read_to_string(&file_name)?;
+pub fn get_user_json_data(store: &mut UserStoreGuard) -> Result<Users> {
+    store.load()
+}
 
-    let user_json: Users = serde_json::from_str(&file.trim())?;
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
 
-    Ok(user_json)
-}
+    let password_hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("hash password error: {}", e))?;
 
-pub fn validate_user(username: &str, password: &str, data_path: &str) -> Result<()> {
-    let user_json_data = get_user_json_data(data_path)?;
+    Ok(password_hash.to_string())
+}
 
-    let res = user_json_data.user_list.iter().find(|x| {
-        if x.name == username && password == x.password {
-            return true;
-        }
-        return false;
-    });
+// 旧版本 users.json 中的密码为明文, 无法解析为 PHC 格式时按明文比较
+pub fn is_password_hashed(stored_password: &str) -> bool {
+    PasswordHash::new(stored_password).is_ok()
+}
 
-    return if let Some(_) = res {
-        Ok(())
-    } else {
-        Err(anyhow!("not correct"))
-    };
+pub fn verify_password(password: &str, stored_password: &str) -> bool {
+    match PasswordHash::new(stored_password) {
+        Ok(parsed_hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+        Err(_) => password == stored_password,
+    }
 }
 
-pub fn update_user_list(user_list: Vec<User>, data_path: &str) -> Result<()> {
-    let file_name = format!("{}/{}", data_path, USERS_JSON_FILE_NAME);
+pub fn validate_user(username: &str, password: &str, store: &mut UserStoreGuard) -> Result<()> {
+    let mut user_json_data = get_user_json_data(store)?;
 
-    let mut user_json: Users = get_user_json_data(&data_path)?;
+    let user = user_json_data
+        .user_list
+        .iter_mut()
+        .find(|x| x.name == username && verify_password(password, &x.password))
+        .ok_or(anyhow!("not correct"))?;
 
-    user_json.user_list = user_list;
+    // 明文密码登录成功后升级为哈希存储
+    if !is_password_hashed(&user.password) {
+        user.password = hash_password(password)?;
+        update_user_list(user_json_data.user_list, store)?;
+        info!("user: {} legacy password upgraded", username);
+    }
 
-    let mut file = fs::File::create(&file_name)?;
+    Ok(())
+}
 
-    let json_str = serde_json::to_string(&user_json)?;
+pub fn update_user_list(user_list: Vec<User>, store: &mut UserStoreGuard) -> Result<()> {
+    let mut user_json: Users = get_user_json_data(store)?;
 
-    file.write_all(json_str.as_bytes())?;
+    user_json.user_list = user_list;
 
-    Ok(())
+    store.save(user_json)
 }
 
-pub fn get_user_info(username: &str, data_path: &str) -> Result<User> {
-    let user_json: Users = get_user_json_data(data_path)?;
+pub fn get_user_info(username: &str, store: &mut UserStoreGuard) -> Result<User> {
+    let user_json: Users = get_user_json_data(store)?;
 
     let res = user_json.user_list.iter().find(|x| x.name == username);
 
@@ -68,14 +84,86 @@ pub fn get_user_info(username: &str, data_path: &str) -> Result<User> {
     }
 }
 
-pub fn check_user_in_user_list(username: &str, data_path: &str) -> Result<bool> {
-    let file_name = format!("{}/{}", data_path, USERS_JSON_FILE_NAME);
+pub fn get_user_by_id(user_id: u64, store: &mut UserStoreGuard) -> Result<User> {
+    let user_json: Users = get_user_json_data(store)?;
+
+    user_json
+        .user_list
+        .into_iter()
+        .find(|x| x.user_id == user_id)
+        .ok_or(anyhow!("User not found"))
+}
+
+// admin 仅允许 0(玩家), 1(管理员), 2(协管)
+pub fn check_admin_value(admin: u8) -> Result<()> {
+    if admin > 2 {
+        return Err(anyhow!("admin value not correct: {}", admin));
+    }
+
+    Ok(())
+}
+
+pub fn create_user(user: User, store: &mut UserStoreGuard) -> Result<()> {
+    check_admin_value(user.admin)?;
+
+    let mut user_json: Users = get_user_json_data(store)?;
+
+    if user_json.user_list.iter().any(|x| x.name == user.name) {
+        return Err(anyhow!("user already exists"));
+    }
+
+    if user_json
+        .user_list
+        .iter()
+        .any(|x| x.user_id == user.user_id)
+    {
+        return Err(anyhow!("user_id already bound: {}", user.user_id));
+    }
+
+    user_json.user_list.push(user);
+
+    update_user_list(user_json.user_list, store)
+}
+
+pub fn delete_user(user_id: u64, store: &mut UserStoreGuard) -> Result<User> {
+    let mut user_json: Users = get_user_json_data(store)?;
+
+    let index = user_json
+        .user_list
+        .iter()
+        .position(|x| x.user_id == user_id)
+        .ok_or(anyhow!("User not found"))?;
+
+    let user = user_json.user_list.remove(index);
+
+    update_user_list(user_json.user_list, store)?;
+
+    Ok(user)
+}
+
+pub fn modify_user<F>(user_id: u64, store: &mut UserStoreGuard, f: F) -> Result<()>
+where
+    F: FnOnce(&mut User, &[User]) -> Result<()>,
+{
+    let mut user_json: Users = get_user_json_data(store)?;
+
+    let index = user_json
+        .user_list
+        .iter()
+        .position(|x| x.user_id == user_id)
+        .ok_or(anyhow!("User not found"))?;
 
-    info!("check_user_in_user_list:: file_name: {}", file_name);
+    let mut user = user_json.user_list.remove(index);
 
-    let file = std::fs::read_to_string(file_name)?;
+    f(&mut user, &user_json.user_list)?;
+
+    user_json.user_list.insert(index, user);
+
+    update_user_list(user_json.user_list, store)
+}
 
-    let user_json: Users = serde_json::from_str(&file)?;
+pub fn check_user_in_user_list(username: &str, store: &mut UserStoreGuard) -> Result<bool> {
+    let user_json: Users = get_user_json_data(store)?;
 
     let res = user_json.user_list.iter().find(|x| x.name == username);
 
@@ -92,19 +180,69 @@ pub fn get_user_id_in_profiles(username: &str, profile_path: &str) -> Result<boo
     Ok(true)
 }
 
-pub fn register_user(username: &str, password: &str, config: &AppData) -> Result<u64> {
-    let is_in_user_list = check_user_in_user_list(username, &config.server_data_folder_path)?;
+// 注册前检查用户名未被使用, 且存档未被其他用户绑定
+pub fn register_user(username: &str, profile_id: u64, store: &mut UserStoreGuard) -> Result<()> {
+    let is_in_user_list = check_user_in_user_list(username, store)?;
 
     if is_in_user_list {
         return Err(anyhow!("user already exists"));
     }
 
-    let profile_id = get_user_profile_id(username, &config.rwr_profile_folder_path);
+    if get_user_by_id(profile_id, store).is_ok() {
+        return Err(anyhow!("profile already bound: {}", profile_id));
+    }
+
+    Ok(())
+}
+
+// 验证码字符集去除了易混淆的 0/O, 1/I
+static REGISTER_CODE_CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+// 验证码不与存档当前的标签相同, 避免无需修改标签即可通过验证
+pub fn new_register_challenge(
+    username: &str,
+    profile_id: u64,
+    password_hash: String,
+    profile_path: &str,
+) -> Result<RegisterChallenge> {
+    let squad_tag = extract_profile(profile_id, profile_path)?.squad_tag;
+
+    let mut rng = rand::thread_rng();
 
-    info!("register_user fn: profile_id: {:?}", profile_id);
+    let code = loop {
+        let code: String = (0..REGISTER_CODE_LEN)
+            .map(|_| REGISTER_CODE_CHARSET[rng.gen_range(0..REGISTER_CODE_CHARSET.len())] as char)
+            .collect();
 
-    return match profile_id {
-        Ok(id) => Ok(id),
-        Err(e) => Err(e),
+        if !squad_tag.trim().eq_ignore_ascii_case(&code) {
+            break code;
+        }
     };
+
+    let mut challenge_id_bytes = [0u8; 16];
+    rng.fill_bytes(&mut challenge_id_bytes);
+
+    Ok(RegisterChallenge {
+        challenge_id: URL_SAFE_NO_PAD.encode(challenge_id_bytes),
+        username: username.to_string(),
+        profile_id,
+        password_hash,
+        code,
+        expire_at: Utc::now().timestamp() + REGISTER_CHALLENGE_EXPIRE_SECS,
+        failed_count: 0,
+    })
+}
+
+// 存档 squad_tag 与验证码一致时证明注册者拥有该存档
+pub fn check_register_challenge(challenge: &RegisterChallenge, profile_path: &str) -> Result<bool> {
+    let profile = extract_profile(challenge.profile_id, profile_path)?;
+
+    if profile.username != challenge.username {
+        return Err(anyhow!("profile username changed"));
+    }
+
+    Ok(profile
+        .squad_tag
+        .trim()
+        .eq_ignore_ascii_case(&challenge.code))
 }
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use crate::model::ResponseJson;
+use actix_web::http::header::{EntityTag, IfMatch};
+use actix_web::HttpResponse;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tracing::warn;
+
+fn write_temp_file(temp_path: &Path, content: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(temp_path)?;
+
+    file.write_all(content)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+// 落盘重命名结果, 避免断电后目录项丢失
+#[cfg(unix)]
+fn sync_dir(file_path: &Path) -> Result<()> {
+    if let Some(dir_path) = file_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::File::open(dir_path)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_file_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// 先写入同目录临时文件并 fsync, 再重命名覆盖目标文件
+// 写入中断(崩溃, 磁盘已满)时目标文件保持原样, 游戏服务端不会读到写了一半的文件
+pub fn write_file_atomic<P: AsRef<Path>>(file_path: P, content: &[u8]) -> Result<()> {
+    let file_path = file_path.as_ref();
+
+    let file_name = file_path
+        .file_name()
+        .ok_or(anyhow!("path read file_name error: {:?}", file_path))?;
+
+    // 临时文件名带随机后缀, 避免并发写入同一文件时互相覆盖
+    let temp_path = file_path.with_file_name(format!(
+        ".{}.{:08x}.tmp",
+        file_name.to_string_lossy(),
+        rand::thread_rng().gen::<u32>()
+    ));
+
+    if let Err(err) = write_temp_file(&temp_path, content) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    if let Err(err) = fs::rename(&temp_path, file_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+
+    sync_dir(file_path)
+}
+
+pub fn copy_file_atomic<P: AsRef<Path>, Q: AsRef<Path>>(from_path: P, to_path: Q) -> Result<()> {
+    let content = fs::read(from_path)?;
+
+    write_file_atomic(to_path, &content)
+}
+
+// 上传的文件名来自客户端, 不允许包含路径
+pub fn check_upload_file_name(file_name: &str) -> Result<()> {
+    if file_name.is_empty()
+        || file_name == "."
+        || file_name == ".."
+        || file_name.contains(['/', '\\'])
+    {
+        return Err(anyhow!("invalid upload file name: {}", file_name));
+    }
+
+    Ok(())
+}
+
+// 单次上传独立的临时目录, 并发上传同一存档时互不覆盖, 离开作用域时删除
+pub struct UploadTempFolder {
+    pub path: String,
+}
+
+impl UploadTempFolder {
+    pub fn create(temp_folder_path: &str, id: u64) -> Result<Self> {
+        let path = format!(
+            "{}/{}.{:016x}",
+            temp_folder_path,
+            id,
+            rand::thread_rng().gen::<u64>()
+        );
+
+        fs::create_dir_all(&path)?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for UploadTempFolder {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.path) {
+            warn!("remove upload temp folder {} error: {:?}", self.path, err);
+        }
+    }
+}
+
+// 由文件内容计算 ETag, 游戏服务端写入存档后 ETag 随之变化
+pub fn get_file_etag<P: AsRef<Path>>(file_path: P) -> Result<EntityTag> {
+    let content = fs::read(file_path)?;
+
+    let hash = Sha256::digest(&content);
+
+    Ok(EntityTag::new_strong(URL_SAFE_NO_PAD.encode(&hash[..16])))
+}
+
+// 未携带 If-Match 时不做校验; 携带时文件当前 ETag 需与其中之一一致, 否则返回 412
+pub fn check_file_if_match<P: AsRef<Path>>(
+    file_path: P,
+    if_match: Option<&IfMatch>,
+) -> Result<(), HttpResponse> {
+    let is_match = match if_match {
+        None => return Ok(()),
+        Some(IfMatch::Items(etag_list)) if etag_list.is_empty() => return Ok(()),
+        Some(IfMatch::Any) => file_path.as_ref().exists(),
+        Some(IfMatch::Items(etag_list)) => get_file_etag(&file_path)
+            .map(|etag| etag_list.iter().any(|x| x.strong_eq(&etag)))
+            .unwrap_or(false),
+    };
+
+    if is_match {
+        return Ok(());
+    }
+
+    Err(HttpResponse::PreconditionFailed().json(
+        ResponseJson::default().set_precondition_failed_msg("file changed, reload and retry"),
+    ))
+}
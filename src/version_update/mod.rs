@@ -1,2 +1,7 @@
+pub mod model;
 pub mod preupdate;
-mod update_to_154;
+pub mod registry;
+pub mod service;
+pub mod store;
+pub mod update_to_154;
+pub mod utils;
@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use crate::backup::model::BackupKind;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+// 单个存档格式升级步骤, 输入 from_version 的存档内容, 返回 to_version 的存档内容
+#[derive(Debug, Clone, Copy)]
+pub struct MigrationStep {
+    pub kind: BackupKind,
+    pub from_version: u16,
+    pub to_version: u16,
+    // 用于升级报告, 说明该步骤修改的内容
+    pub description: &'static str,
+    pub migrate: fn(&str) -> Result<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MigrationFileRes {
+    pub id: u64,
+    pub kind: BackupKind,
+    // 依次执行的步骤说明, 如 "153 -> 154: ..."
+    pub step_list: Vec<String>,
+    // 升级失败时的错误信息, 失败的存档保持不变
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// 启动时的存档升级报告, 写入 server_data_folder_path/migration_report.json
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MigrationReport {
+    // dry-run 模式下只生成报告, 不修改存档
+    pub dry_run: bool,
+    pub created_at: i64,
+    // 升级前已保存原始存档的批次 id, 可用于回滚
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migration_id: Option<u64>,
+    // 已升级(dry-run 时为需要升级)的存档数
+    pub person_count: usize,
+    pub profile_count: usize,
+    pub error_count: usize,
+    // 仅包含需要升级或升级失败的存档
+    pub file_list: Vec<MigrationFileRes>,
+    // 文件名无法解析为 id 而跳过的文件
+    #[serde(default)]
+    pub invalid_name_list: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MigrationFile {
+    pub id: u64,
+    pub kind: BackupKind,
+}
+
+// 一次启动升级的记录, 保存在 migrations/{migration_id}/migration.json
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MigrationItem {
+    // 升级时间 unix 毫秒时间戳, 同时作为 id
+    pub migration_id: u64,
+    pub created_at: i64,
+    pub file_list: Vec<MigrationFile>,
+    pub rolled_back_at: Option<i64>,
+}
@@ -1,103 +1,226 @@
-use std::borrow::Borrow;
-
-use super::update_to_154;
+use super::model::{MigrationFile, MigrationFileRes, MigrationReport};
+use super::store::MigrationStore;
+use super::utils::migrate_file;
 use crate::{
-    person::extract::extract_person,
-    person::model::{ItemTag, OrderTag, Person, StashItemTag},
-    profile::{extract::extract_profile, model::Profile},
+    backup::model::BackupKind, constant::MIGRATION_REPORT_FILE_NAME,
+    person::async_extract::async_scan_all_id, utils::write_file_atomic,
 };
-use anyhow::{anyhow, Result};
-use futures::{self, FutureExt, SinkExt};
-use rayon::prelude::*;
+use anyhow::Result;
+use chrono::prelude::*;
+use futures;
+use std::path::Path;
 use tokio;
-use tracing::{error, info};
-
-async fn query_all_data(folder_path: String) -> Result<()> {
-    let mut folder_entries = tokio::fs::read_dir(&folder_path).await?;
-
-    let mut entries: Vec<u64> = Vec::new();
-
-    while let Some(entry) = folder_entries.next_entry().await? {
-        let file_name = entry.file_name();
-        let file_name_str = String::from(file_name.to_str().unwrap());
-
-        if file_name_str.ends_with(".profile") {
-            let vec = file_name_str.split(".").collect::<Vec<_>>();
-
-            let parse_res = vec.first().unwrap().parse();
-
-            if let Ok(id) = parse_res {
-                entries.push(id);
-            }
-        }
-    }
-
-    let person_future_vec = entries
-        .clone()
+use tracing::{error, info, warn};
+
+// 按注册的升级步骤升级全部存档, 单个存档失败时记录到报告中并跳过
+async fn migrate_all_file(
+    folder_path: &str,
+    kind: BackupKind,
+    id_list: Vec<u64>,
+    dry_run: bool,
+) -> Result<Vec<MigrationFileRes>> {
+    let migrate_future_vec = id_list
         .into_iter()
         .map(|id| {
-            let cloned_folder_path = folder_path.clone();
+            let cloned_folder_path = folder_path.to_string();
 
             return tokio::spawn(async move {
-                let person = extract_person(id, &cloned_folder_path);
-                match person {
-                    Ok(p) => {
-                        if (p.version < 154) {
-                            // update from 153 to 154
-                            update_to_154::update_person(&cloned_folder_path, id)
-                                .await
-                                .unwrap();
-                        }
-                        Ok(())
-                    }
+                match migrate_file(&cloned_folder_path, id, kind, dry_run) {
+                    Ok(step_list) => MigrationFileRes {
+                        id,
+                        kind,
+                        step_list: step_list
+                            .iter()
+                            .map(|step| {
+                                format!(
+                                    "{} -> {}: {}",
+                                    step.from_version, step.to_version, step.description
+                                )
+                            })
+                            .collect(),
+                        error: None,
+                    },
                     Err(e) => {
-                        error!("error in extract_person call: {}, {:?}", id, e);
-                        Err(e)
+                        error!("error in migrate_file call: {} {:?}, {:?}", id, kind, e);
+                        MigrationFileRes {
+                            id,
+                            kind,
+                            step_list: vec![],
+                            error: Some(e.to_string()),
+                        }
                     }
                 }
             });
         })
         .collect::<Vec<_>>();
 
-    let profile_future_vec = entries
-        .clone()
+    let migrate_vec = futures::future::try_join_all(migrate_future_vec).await?;
+
+    Ok(migrate_vec
         .into_iter()
-        .map(|id| {
-            let cloned_folder_path = folder_path.clone();
+        .filter(|res| !res.step_list.is_empty() || res.error.is_some())
+        .collect())
+}
 
-            return tokio::spawn(async move {
-                let profile = extract_profile(id, &cloned_folder_path);
-                match profile {
-                    Ok(p) => {
-                        if p.game_version < 154 {
-                            // update from 153 to 154
-                            update_to_154::update_profile(&cloned_folder_path, id)
-                                .await
-                                .unwrap();
-                        }
-                        Ok(())
-                    }
-                    Err(e) => {
-                        error!("error in extract_profile call: {}, {:?}", id, e);
-                        Err(e)
-                    }
-                }
-            });
+pub async fn preupdate(
+    folder_path: String,
+    data_folder_path: &str,
+    dry_run: bool,
+    migration_store: &MigrationStore,
+) -> Result<MigrationReport> {
+    info!("Starting preupdate, dry_run: {}", dry_run);
+
+    // 先检查需要升级的存档, 不修改文件
+    let mut file_list = vec![];
+
+    let mut invalid_name_list = vec![];
+
+    let (person_id_list, person_invalid_name_list) =
+        async_scan_all_id(&folder_path, ".person").await?;
+    file_list
+        .extend(migrate_all_file(&folder_path, BackupKind::Person, person_id_list, true).await?);
+    invalid_name_list.extend(person_invalid_name_list);
+
+    let (profile_id_list, profile_invalid_name_list) =
+        async_scan_all_id(&folder_path, ".profile").await?;
+    file_list
+        .extend(migrate_all_file(&folder_path, BackupKind::Profile, profile_id_list, true).await?);
+    invalid_name_list.extend(profile_invalid_name_list);
+
+    if !invalid_name_list.is_empty() {
+        warn!("skip invalid file name: {:?}", invalid_name_list);
+    }
+
+    let pending_list: Vec<MigrationFile> = file_list
+        .iter()
+        .filter(|res| res.error.is_none())
+        .map(|res| MigrationFile {
+            id: res.id,
+            kind: res.kind,
         })
-        .collect::<Vec<_>>();
+        .collect();
 
-    let person_vec = futures::future::try_join_all(person_future_vec).await?;
-    let profile_vec = futures::future::try_join_all(profile_future_vec).await?;
+    let mut migration_id = None;
 
-    Ok(())
-}
+    if !dry_run && !pending_list.is_empty() {
+        // 升级前保存全部原始存档, 保存失败时不进行升级
+        let item = migration_store.create(&folder_path, pending_list)?;
 
-pub async fn preupdate(folder_path: String) -> Result<()> {
-    info!("Starting preupdate...");
+        file_list.retain(|res| res.error.is_some());
 
-    query_all_data(folder_path).await?;
+        for kind in [BackupKind::Person, BackupKind::Profile] {
+            let id_list = item
+                .file_list
+                .iter()
+                .filter(|file| file.kind == kind)
+                .map(|file| file.id)
+                .collect();
 
-    info!("Preupdate Completed.");
+            file_list.extend(migrate_all_file(&folder_path, kind, id_list, false).await?);
+        }
+
+        migration_id = Some(item.migration_id);
+    }
+
+    let count_migrated = |kind: BackupKind| {
+        file_list
+            .iter()
+            .filter(|res| res.kind == kind && res.error.is_none())
+            .count()
+    };
+
+    let report = MigrationReport {
+        dry_run,
+        created_at: Utc::now().timestamp(),
+        migration_id,
+        person_count: count_migrated(BackupKind::Person),
+        profile_count: count_migrated(BackupKind::Profile),
+        error_count: file_list.iter().filter(|res| res.error.is_some()).count(),
+        file_list,
+        invalid_name_list,
+    };
+
+    // 报告写入失败不影响启动
+    let report_path = Path::new(data_folder_path).join(MIGRATION_REPORT_FILE_NAME);
+    if let Err(err) = write_file_atomic(
+        &report_path,
+        serde_json::to_string_pretty(&report)?.as_bytes(),
+    ) {
+        error!("write migration report error: {:?}", err);
+    }
 
-    Ok(())
+    info!(
+        "Preupdate Completed, person: {}, profile: {}, error: {}, report: {:?}",
+        report.person_count, report.profile_count, report.error_count, report_path
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::preupdate;
+    use crate::backup::store::BackupStore;
+    use crate::version_update::store::MigrationStore;
+    use crate::xml::extract::read_fixture_list;
+    use std::fs;
+
+    #[tokio::test]
+    async fn migrate_and_rollback() {
+        let folder = std::env::temp_dir().join(format!("rwr_rollback_{}", std::process::id()));
+        let profile_folder = folder.join("profiles");
+        let data_folder = folder.join("data");
+        fs::create_dir_all(&profile_folder).unwrap();
+        fs::create_dir_all(&data_folder).unwrap();
+
+        let person = read_fixture_list("person/v153").remove(0).1;
+        let profile = read_fixture_list("profile/v153").remove(0).1;
+        fs::write(profile_folder.join("1.person"), &person).unwrap();
+        fs::write(profile_folder.join("1.profile"), &profile).unwrap();
+        fs::write(
+            profile_folder.join("2.person"),
+            &read_fixture_list("person")[0].1,
+        )
+        .unwrap();
+        // 文件名无法解析为 id 的文件跳过并写入报告
+        fs::write(profile_folder.join("foo.person"), &person).unwrap();
+        fs::write(profile_folder.join("1.bak.profile"), &profile).unwrap();
+
+        let profile_folder_path = profile_folder.to_str().unwrap();
+        let data_folder_path = data_folder.to_str().unwrap();
+        let migration_store = MigrationStore::new(data_folder_path);
+
+        let report = preupdate(
+            profile_folder_path.to_string(),
+            data_folder_path,
+            false,
+            &migration_store,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!((report.person_count, report.profile_count), (1, 1));
+        assert!(report.migration_id.is_some());
+        assert_eq!(report.invalid_name_list, ["foo.person", "1.bak.profile"]);
+        assert_ne!(
+            fs::read_to_string(profile_folder.join("1.person")).unwrap(),
+            person
+        );
+
+        let backup_store = BackupStore::new(data_folder_path, 0, 0);
+        let item = migration_store
+            .rollback_last(profile_folder_path, &backup_store)
+            .unwrap();
+        let rollback_again = migration_store.rollback_last(profile_folder_path, &backup_store);
+
+        let rolled_back_person = fs::read_to_string(profile_folder.join("1.person")).unwrap();
+        let rolled_back_profile = fs::read_to_string(profile_folder.join("1.profile")).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(Some(item.migration_id), report.migration_id);
+        assert_eq!(item.file_list.len(), 2);
+        assert_eq!(rolled_back_person, person);
+        assert_eq!(rolled_back_profile, profile);
+        assert!(rollback_again.is_err());
+    }
 }
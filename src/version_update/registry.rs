@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::MigrationStep;
+use super::update_to_154;
+use crate::backup::model::BackupKind;
+use crate::constant::{MAX_PERSON_FILE_VERSION, MAX_PROFILE_FILE_VERSION};
+
+// 全部升级步骤, 新版本格式变化时在此追加
+pub static MIGRATION_STEP_LIST: [MigrationStep; 2] = [
+    MigrationStep {
+        kind: BackupKind::Person,
+        from_version: 153,
+        to_version: 154,
+        description: "stash/backpack item grouped into item_group by key",
+        migrate: update_to_154::migrate_person,
+    },
+    MigrationStep {
+        kind: BackupKind::Profile,
+        from_version: 153,
+        to_version: 154,
+        description: "game_version updated only",
+        migrate: update_to_154::migrate_profile,
+    },
+];
+
+pub fn get_target_version(kind: BackupKind) -> u16 {
+    match kind {
+        BackupKind::Person => MAX_PERSON_FILE_VERSION,
+        BackupKind::Profile => MAX_PROFILE_FILE_VERSION,
+    }
+}
+
+pub fn find_step(kind: BackupKind, version: u16) -> Option<&'static MigrationStep> {
+    MIGRATION_STEP_LIST
+        .iter()
+        .find(|step| step.kind == kind && step.from_version == version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_target_version, MIGRATION_STEP_LIST};
+    use crate::backup::model::BackupKind;
+    use crate::version_update::utils::{get_content_version, migrate_content};
+    use crate::xml::extract::read_fixture_list;
+
+    // 每个升级步骤使用 tests/fixtures/{kind}/v{from_version} 下的存档测试
+    #[test]
+    fn migrate_fixture_list() {
+        for step in MIGRATION_STEP_LIST.iter() {
+            assert!(step.from_version < step.to_version, "{:?}", step);
+
+            let kind = format!("{}/v{}", step.kind.extension(), step.from_version);
+            let fixture_list = read_fixture_list(&kind);
+            assert!(!fixture_list.is_empty(), "no fixture: {}", kind);
+
+            for (name, content) in fixture_list {
+                let migrated = (step.migrate)(&content).unwrap();
+
+                assert_eq!(
+                    get_content_version(step.kind, &migrated).unwrap(),
+                    step.to_version,
+                    "fixture: {}/{}",
+                    kind,
+                    name
+                );
+
+                // 由注册表依次升级到当前版本
+                let (migrated, step_list) = migrate_content(step.kind, &content).unwrap();
+                assert!(!step_list.is_empty());
+                assert_eq!(
+                    get_content_version(step.kind, &migrated).unwrap(),
+                    get_target_version(step.kind)
+                );
+                assert!(migrate_content(step.kind, &migrated).unwrap().1.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn migrate_unknown_version() {
+        let (_, content) = read_fixture_list("profile").remove(0);
+        let content = content.replace(r#"game_version="154""#, r#"game_version="100""#);
+
+        let err = migrate_content(BackupKind::Profile, &content).unwrap_err();
+        assert!(err.to_string().contains("unsupported version"), "{}", err);
+    }
+}
@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use crate::audit::utils::audit_change_list;
+use crate::auth::model::{Identity, Role};
+use crate::auth::utils::check_role;
+use crate::{model::ResponseJson, AppData};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use tracing::{error, info, instrument};
+
+pub fn migration_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/migration")
+            .service(query_all_migration)
+            .service(rollback_last_migration),
+    );
+}
+
+#[instrument]
+#[get("/query_all")]
+async fn query_all_migration(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    return match config.migration_store.query_all() {
+        Ok(migration_list) => HttpResponse::Ok().json(migration_list),
+        Err(err) => {
+            error!("query all migration error: {:?}", err);
+            HttpResponse::BadRequest()
+                .json(ResponseJson::default().set_err_msg("query all migration error"))
+        }
+    };
+}
+
+// 回滚期间持有存档目录锁, 暂停其他存档修改
+#[instrument]
+#[post("/rollback")]
+async fn rollback_last_migration(config: web::Data<AppData>, identity: Identity) -> impl Responder {
+    info!("");
+    if let Err(res) = check_role(&identity, Role::Admin) {
+        return res;
+    }
+
+    let _folder_lock = config.profile_lock_registry.lock_folder().await;
+
+    return match config
+        .migration_store
+        .rollback_last(&config.rwr_profile_folder_path, &config.backup_store)
+    {
+        Ok(item) => {
+            info!(
+                "admin: {} rollback migration {} successful",
+                identity.name, item.migration_id
+            );
+
+            let change_list = item
+                .file_list
+                .iter()
+                .map(|file| {
+                    (
+                        file.id,
+                        vec![format!(
+                            "rollback {} migration: {}",
+                            file.kind.extension(),
+                            item.migration_id
+                        )],
+                    )
+                })
+                .collect();
+
+            audit_change_list(&config, &identity, "/migration/rollback", change_list).await;
+
+            HttpResponse::Ok().json(item)
+        }
+        Err(err) => {
+            error!("rollback migration error: {:?}", err);
+            HttpResponse::BadRequest().json(ResponseJson::default().set_err_msg(&err.to_string()))
+        }
+    };
+}
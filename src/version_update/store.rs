@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{MigrationFile, MigrationItem};
+use crate::backup::store::BackupStore;
+use crate::constant::{MIGRATION_FOLDER_NAME, MIGRATION_JSON_FILE_NAME};
+use crate::utils::{copy_file_atomic, write_file_atomic};
+use anyhow::{anyhow, Result};
+use chrono::prelude::*;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+// 存档升级前的原始文件, 用于回滚
+// 目录结构: {server_data_folder_path}/migrations/{migration_id}/{id}.person
+#[derive(Debug, Clone)]
+pub struct MigrationStore {
+    folder_path: PathBuf,
+}
+
+impl MigrationStore {
+    pub fn new(data_path: &str) -> Self {
+        Self {
+            folder_path: Path::new(data_path).join(MIGRATION_FOLDER_NAME),
+        }
+    }
+
+    fn get_migration_folder(&self, migration_id: u64) -> PathBuf {
+        self.folder_path.join(migration_id.to_string())
+    }
+
+    fn save_item(&self, item: &MigrationItem) -> Result<()> {
+        let file_path = self
+            .get_migration_folder(item.migration_id)
+            .join(MIGRATION_JSON_FILE_NAME);
+
+        write_file_atomic(file_path, serde_json::to_string(item)?.as_bytes())
+    }
+
+    fn get_item(&self, migration_id: u64) -> Result<MigrationItem> {
+        let file_path = self
+            .get_migration_folder(migration_id)
+            .join(MIGRATION_JSON_FILE_NAME);
+
+        let content = fs::read_to_string(file_path)
+            .map_err(|err| anyhow!("migration not found: {}, {}", migration_id, err))?;
+
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    // 复制即将升级的全部存档, 返回本次升级记录
+    pub fn create(
+        &self,
+        profile_folder_path: &str,
+        file_list: Vec<MigrationFile>,
+    ) -> Result<MigrationItem> {
+        let mut migration_id = Utc::now().timestamp_millis() as u64;
+        while self.get_migration_folder(migration_id).exists() {
+            migration_id += 1;
+        }
+
+        let migration_folder = self.get_migration_folder(migration_id);
+        fs::create_dir_all(&migration_folder)?;
+
+        for file in &file_list {
+            let file_name = format!("{}.{}", file.id, file.kind.extension());
+
+            copy_file_atomic(
+                Path::new(profile_folder_path).join(&file_name),
+                migration_folder.join(&file_name),
+            )?;
+        }
+
+        let item = MigrationItem {
+            migration_id,
+            created_at: (migration_id / 1000) as i64,
+            file_list,
+            rolled_back_at: None,
+        };
+
+        self.save_item(&item)?;
+
+        info!(
+            "migration {} saved {} file(s) to {:?}",
+            migration_id,
+            item.file_list.len(),
+            migration_folder
+        );
+
+        Ok(item)
+    }
+
+    // 按时间倒序返回全部升级记录
+    pub fn query_all(&self) -> Result<Vec<MigrationItem>> {
+        if !self.folder_path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut migration_list = vec![];
+
+        for entry in fs::read_dir(&self.folder_path)? {
+            let entry = entry?;
+
+            let migration_id: u64 = match entry.file_name().to_string_lossy().parse() {
+                Ok(migration_id) => migration_id,
+                Err(_) => continue,
+            };
+
+            // 保存原始存档中途失败时没有 migration.json, 跳过
+            if !entry.path().join(MIGRATION_JSON_FILE_NAME).exists() {
+                continue;
+            }
+
+            migration_list.push(self.get_item(migration_id)?);
+        }
+
+        migration_list.sort_by_key(|item| Reverse(item.migration_id));
+
+        Ok(migration_list)
+    }
+
+    // 将最近一次升级的存档恢复为升级前的文件, 恢复前备份当前存档
+    pub fn rollback_last(
+        &self,
+        profile_folder_path: &str,
+        backup_store: &BackupStore,
+    ) -> Result<MigrationItem> {
+        let mut item = self
+            .query_all()?
+            .into_iter()
+            .next()
+            .ok_or(anyhow!("no migration found"))?;
+
+        if item.rolled_back_at.is_some() {
+            return Err(anyhow!(
+                "migration {} already rolled back",
+                item.migration_id
+            ));
+        }
+
+        let migration_folder = self.get_migration_folder(item.migration_id);
+
+        for file in &item.file_list {
+            let file_name = format!("{}.{}", file.id, file.kind.extension());
+
+            backup_store.backup(profile_folder_path, file.id, file.kind)?;
+
+            copy_file_atomic(
+                migration_folder.join(&file_name),
+                Path::new(profile_folder_path).join(&file_name),
+            )?;
+        }
+
+        item.rolled_back_at = Some(Utc::now().timestamp());
+        self.save_item(&item)?;
+
+        info!(
+            "migration {} rolled back {} file(s)",
+            item.migration_id,
+            item.file_list.len()
+        );
+
+        Ok(item)
+    }
+}
@@ -1,18 +1,23 @@
 use crate::{
     constant::{MAX_DEFAULT_BACKPACK_LEN, MAX_DEFAULT_STASH_LEN},
+    person::extract::{extract_item, extract_order},
     person::model::{ItemGroupTag, ItemTag, OrderTag, Person},
-    person::save::save_person_to_file,
+    person::save::save_person,
+    xml::{
+        error::ParseError,
+        extract::extract_element_tree,
+        model::{XmlElement, XmlExtra, XmlNode},
+    },
 };
-use anyhow::{anyhow, Result};
-use quick_xml::{events::Event, Reader};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use std::{collections::HashMap, fs, io, str};
+use anyhow::Result;
 use regex::Regex;
+use std::collections::HashMap;
 
-struct StashItemTag {
+pub struct StashItemTag {
     pub class: i8,
     pub index: i32,
     pub key: String,
+    pub extra: XmlExtra,
 }
 
 impl Default for StashItemTag {
@@ -21,11 +26,12 @@ impl Default for StashItemTag {
             class: 0,
             index: -1,
             key: "".to_string(),
+            extra: XmlExtra::default(),
         }
     }
 }
 
-struct Person_V153 {
+pub struct PersonV153 {
     pub max_authority_reached: f32,
     pub authority: f32,
     pub job_points: f32,
@@ -47,9 +53,13 @@ struct Person_V153 {
     pub stash_hard_capacity: u16,
     pub backpack_item_list: Vec<StashItemTag>,
     pub stash_item_list: Vec<StashItemTag>,
+    // 未识别的属性与子节点, 转换后原样保留
+    pub extra: XmlExtra,
+    pub stash_extra: XmlExtra,
+    pub backpack_extra: XmlExtra,
 }
 
-impl Default for Person_V153 {
+impl Default for PersonV153 {
     fn default() -> Self {
         Self {
             max_authority_reached: 0.0,
@@ -69,305 +79,199 @@ impl Default for Person_V153 {
             stash_hard_capacity: MAX_DEFAULT_STASH_LEN,
             backpack_item_list: vec![],
             stash_item_list: vec![],
+            extra: XmlExtra::default(),
+            stash_extra: XmlExtra::default(),
+            backpack_extra: XmlExtra::default(),
         }
     }
 }
 
-pub async fn update_person(folder_path: &str, id: u64) -> Result<()> {
-    let mut person = Person_V153::default();
+// 1.53 中 stash/backpack 内每件物品为单独的 item, 没有数量
+fn extract_stash_item(element: XmlElement) -> Result<StashItemTag, ParseError> {
+    let mut item_tag = StashItemTag::default();
 
-    let path = format!("{}/{}.person", folder_path, id);
-
-    let mut reader = Reader::from_file(path)?;
-
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-
-    let mut is_in_person = false;
-    let mut is_in_stash = false;
-    let mut is_in_backpack = false;
-
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Event::Start(e)) => {
-                // println!("start e.name: {:?}", str::from_utf8(e.name()).unwrap());
-                match e.name() {
-                    b"person" => {
-                        is_in_person = true;
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            match attr_key {
-                                b"max_authority_reached" => {
-                                    person.max_authority_reached = attr_value.parse()?;
-                                }
-                                b"authority" => {
-                                    person.authority = attr_value.parse()?;
-                                }
-                                b"job_points" => {
-                                    person.job_points = attr_value.parse()?;
-                                }
-                                b"faction" => {
-                                    person.faction = attr_value;
-                                }
-                                b"name" => {
-                                    person.name = attr_value;
-                                }
-                                b"version" => {
-                                    person.version = attr_value.parse()?;
-                                }
-                                b"alive" => {
-                                    person.alive = attr_value.parse()?;
-                                }
-                                b"soldier_group_id" => {
-                                    person.soldier_group_id = attr_value.parse()?;
-                                }
-                                b"soldier_group_name" => {
-                                    person.soldier_group_name = attr_value;
-                                }
-                                b"block" => {
-                                    person.block = attr_value;
-                                }
-                                b"squad_size_setting" => {
-                                    person.squad_size_setting = attr_value.parse()?;
-                                }
-                                b"order" => {
-                                    let mut order_item = OrderTag::default();
-                                }
-                                _ => (),
-                            }
-                        }
-                        // println!("This is person");
-                    }
-                    b"stash" => {
-                        is_in_stash = true;
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            match attr_key {
-                                b"hard_capacity" => {
-                                    person.stash_hard_capacity = attr_value.parse()?;
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    b"backpack" => {
-                        is_in_backpack = true;
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            match attr_key {
-                                b"hard_capacity" => {
-                                    person.backpack_hard_capacity = attr_value.parse()?;
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    _ => (),
-                }
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "class" => {
+                item_tag.class = attr.parse()?;
             }
-            Ok(Event::Empty(e)) => {
-                // println!("empty e.name: {:?}", str::from_utf8(e.name())?);
-
-                match e.name() {
-                    b"order" => {
-                        let mut order_item = OrderTag::default();
-
-                        for attr in e.attributes() {
-                            let attr_unwrap_res = attr?;
-                            let attr_value = attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                            let attr_key = attr_unwrap_res.key;
-
-                            // println!("attr: {}, value: {}", str::from_utf8(attr_key)?, attr_value);
-
-                            match attr_key {
-                                b"moving" => {
-                                    order_item.moving = attr_value.parse()?;
-                                }
-                                b"target" => {
-                                    order_item.target = attr_value;
-                                }
-                                b"class" => {
-                                    order_item.class = attr_value.parse()?;
-                                }
-                                _ => (),
-                            }
-                        }
-
-                        person.order = order_item;
-                    }
-                    b"item" => {
-                        if is_in_backpack || is_in_stash {
-                            let mut item_tag = StashItemTag::default();
-
-                            for attr in e.attributes() {
-                                let attr_unwrap_res = attr?;
-                                let attr_value =
-                                    attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                                let attr_key = attr_unwrap_res.key;
+            "index" => {
+                item_tag.index = attr.parse()?;
+            }
+            "key" => {
+                item_tag.key = attr.value;
+            }
+            _ => item_tag.extra.attr_list.push(attr),
+        }
+    }
 
-                                // println!(
-                                //     "attr: {}, value: {}",
-                                //     str::from_utf8(attr_key)?,
-                                //     attr_value
-                                // );
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        item_tag.extra.push_child(index, node);
+    }
 
-                                match attr_key {
-                                    b"class" => {
-                                        item_tag.class = attr_value.parse()?;
-                                    }
-                                    b"index" => {
-                                        item_tag.index = attr_value.parse()?;
-                                    }
-                                    b"key" => {
-                                        item_tag.key = attr_value;
-                                    }
-                                    _ => (),
-                                }
-                            }
+    Ok(item_tag)
+}
 
-                            if is_in_backpack {
-                                person.backpack_item_list.push(item_tag);
-                            } else {
-                                person.stash_item_list.push(item_tag);
-                            }
-                        } else {
-                            let mut item_tag = ItemTag::default();
+fn extract_stash_item_container(
+    element: XmlElement,
+    hard_capacity: &mut u16,
+    item_list: &mut Vec<StashItemTag>,
+) -> Result<XmlExtra, ParseError> {
+    let mut extra = XmlExtra::default();
+
+    for attr in element.attr_list {
+        match attr.key.as_str() {
+            "hard_capacity" => {
+                *hard_capacity = attr.parse()?;
+            }
+            _ => extra.attr_list.push(attr),
+        }
+    }
 
-                            for attr in e.attributes() {
-                                let attr_unwrap_res = attr?;
-                                let attr_value =
-                                    attr_unwrap_res.unescape_and_decode_value(&reader)?;
-                                let attr_key = attr_unwrap_res.key;
+    for (index, node) in element.child_list.into_iter().enumerate() {
+        match node {
+            XmlNode::Element(element) if element.name == "item" => {
+                item_list.push(extract_stash_item(element)?);
+            }
+            node => extra.push_child(index, node),
+        }
+    }
 
-                                // println!(
-                                //     "attr: {}, value: {}",
-                                //     str::from_utf8(attr_key)?,
-                                //     attr_value
-                                // );
+    Ok(extra)
+}
 
-                                match attr_key {
-                                    b"slot" => {
-                                        item_tag.slot = attr_value.parse()?;
-                                    }
-                                    b"index" => {
-                                        item_tag.index = attr_value.parse()?;
-                                    }
-                                    b"amount" => {
-                                        item_tag.amount = attr_value.parse()?;
-                                    }
-                                    b"key" => {
-                                        item_tag.key = attr_value;
-                                    }
-                                    _ => (),
-                                }
-                            }
+pub fn extract_person_v153(root: XmlElement) -> Result<PersonV153, ParseError> {
+    let mut person = PersonV153::default();
 
-                            person.item_list.push(item_tag);
-                        }
-                    }
-                    b"backpack" => {}
-                    _ => (),
-                }
+    for attr in root.attr_list {
+        match attr.key.as_str() {
+            "max_authority_reached" => {
+                person.max_authority_reached = attr.parse()?;
             }
-            Ok(Event::End(e)) => {
-                // DEBUG
-                // println!("end e.name: {:?}", str::from_utf8(e.name())?);
+            "authority" => {
+                person.authority = attr.parse()?;
             }
-            Ok(Event::Eof) => break,
-            Err(e) => panic!("Error at position {} : {:?}", reader.buffer_position(), e),
-            _ => (),
+            "job_points" => {
+                person.job_points = attr.parse()?;
+            }
+            "faction" => {
+                person.faction = attr.value;
+            }
+            "name" => {
+                person.name = attr.value;
+            }
+            "version" => {
+                person.version = attr.parse()?;
+            }
+            "alive" => {
+                person.alive = attr.parse()?;
+            }
+            "soldier_group_id" => {
+                person.soldier_group_id = attr.parse()?;
+            }
+            "soldier_group_name" => {
+                person.soldier_group_name = attr.value;
+            }
+            "block" => {
+                person.block = attr.value;
+            }
+            "squad_size_setting" => {
+                person.squad_size_setting = attr.parse()?;
+            }
+            _ => person.extra.attr_list.push(attr),
         }
     }
 
-    let mut new_person: Person = Person::default();
-
-    new_person.max_authority_reached = person.max_authority_reached;
-    new_person.authority = person.authority;
-    new_person.job_points = person.job_points;
-    new_person.faction = person.faction;
-    new_person.name = person.name;
-    new_person.alive = person.alive;
-    new_person.soldier_group_id = person.soldier_group_id;
-    new_person.soldier_group_name = person.soldier_group_name;
-    new_person.order = person.order;
-    new_person.item_list = person.item_list;
-    new_person.stash_hard_capacity = person.stash_hard_capacity;
-    new_person.backpack_hard_capacity = person.backpack_hard_capacity;
-
-    let mut backpack_item_map: HashMap<String, ItemGroupTag> =
-        HashMap::with_capacity(person.backpack_item_list.len());
-    for backpack_item in person.backpack_item_list {
-        backpack_item_map
-            .entry(backpack_item.key.clone())
-            .and_modify(|item| item.amount += 1)
-            .or_insert(ItemGroupTag {
-                index: backpack_item.index,
-                class: backpack_item.class,
-                amount: 1,
-                key: backpack_item.key,
-            });
+    for (index, node) in root.child_list.into_iter().enumerate() {
+        match node {
+            XmlNode::Element(element) if element.name == "order" => {
+                person.order = extract_order(element)?;
+            }
+            XmlNode::Element(element) if element.name == "item" => {
+                person.item_list.push(extract_item(element)?);
+            }
+            XmlNode::Element(element) if element.name == "stash" => {
+                person.stash_extra = extract_stash_item_container(
+                    element,
+                    &mut person.stash_hard_capacity,
+                    &mut person.stash_item_list,
+                )?;
+            }
+            XmlNode::Element(element) if element.name == "backpack" => {
+                person.backpack_extra = extract_stash_item_container(
+                    element,
+                    &mut person.backpack_hard_capacity,
+                    &mut person.backpack_item_list,
+                )?;
+            }
+            node => person.extra.push_child(index, node),
+        }
     }
 
-    let item_group_list: Vec<ItemGroupTag> = backpack_item_map
-        .values()
-        .map(|item| item.clone())
-        .collect::<Vec<ItemGroupTag>>();
-    new_person.backpack_item_list = item_group_list;
+    Ok(person)
+}
 
-    let mut stash_item_map: HashMap<String, ItemGroupTag> =
-        HashMap::with_capacity(person.stash_item_list.len());
-    for stash_item in person.stash_item_list {
-        stash_item_map
-            .entry(stash_item.key.clone())
-            .and_modify(|item| item.amount += 1)
-            .or_insert(ItemGroupTag {
-                index: stash_item.index,
-                class: stash_item.class,
-                amount: 1,
-                key: stash_item.key,
-            });
+// 相同 key 的物品合并为一个 item_group, 保持首次出现的顺序与未识别的属性
+fn group_stash_item_list(stash_item_list: Vec<StashItemTag>) -> Vec<ItemGroupTag> {
+    let mut index_map: HashMap<String, usize> = HashMap::with_capacity(stash_item_list.len());
+    let mut item_list: Vec<ItemGroupTag> = Vec::with_capacity(stash_item_list.len());
+
+    for stash_item in stash_item_list {
+        match index_map.get(&stash_item.key) {
+            Some(&index) => item_list[index].amount += 1,
+            None => {
+                index_map.insert(stash_item.key.clone(), item_list.len());
+                item_list.push(ItemGroupTag {
+                    index: stash_item.index,
+                    class: stash_item.class,
+                    amount: 1,
+                    key: stash_item.key,
+                    extra: stash_item.extra,
+                });
+            }
+        }
     }
 
-    let item_group_list: Vec<ItemGroupTag> = stash_item_map
-        .values()
-        .map(|item| item.clone())
-        .collect::<Vec<ItemGroupTag>>();
-    new_person.stash_item_list = item_group_list;
-
-    save_person_to_file(folder_path, id, &new_person)?;
-
-    Ok(())
+    item_list
 }
 
-pub async fn update_profile(folder_path: &str, id: u64) -> Result<()> {
-    // replace game_version text only
-
-    let path = format!("{}/{}.profile", folder_path, id);
-
-    let file_content = tokio::fs::read_to_string(path.clone()).await?;
-
-    let search_str = r#"game_version="\d+""#;
-
-    let re = Regex::new(search_str).unwrap();
+impl From<PersonV153> for Person {
+    fn from(person: PersonV153) -> Self {
+        Person {
+            max_authority_reached: person.max_authority_reached,
+            authority: person.authority,
+            job_points: person.job_points,
+            faction: person.faction,
+            name: person.name,
+            alive: person.alive,
+            soldier_group_id: person.soldier_group_id,
+            soldier_group_name: person.soldier_group_name,
+            block: person.block,
+            squad_size_setting: person.squad_size_setting,
+            order: person.order,
+            item_list: person.item_list,
+            stash_hard_capacity: person.stash_hard_capacity,
+            backpack_hard_capacity: person.backpack_hard_capacity,
+            backpack_item_list: group_stash_item_list(person.backpack_item_list),
+            stash_item_list: group_stash_item_list(person.stash_item_list),
+            extra: person.extra,
+            stash_extra: person.stash_extra,
+            backpack_extra: person.backpack_extra,
+            ..Person::default()
+        }
+    }
+}
 
-    let new_text = "game_version=\"154\"";
+// 1.53 -> 1.54: stash/backpack 中的 item 按 key 合并为 item_group
+pub fn migrate_person(content: &str) -> Result<String> {
+    let person: Person = extract_person_v153(extract_element_tree(content)?)?.into();
 
-    let result = re.replace(&file_content, new_text).to_string();
+    save_person(&person)
+}
 
-    tokio::fs::write(path, result).await?;
+// 1.53 -> 1.54: 仅修改 game_version
+pub fn migrate_profile(content: &str) -> Result<String> {
+    let re = Regex::new(r#"game_version="\d+""#)?;
 
-    Ok(())
+    Ok(re.replace(content, r#"game_version="154""#).to_string())
 }
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::MigrationStep;
+use super::registry::{find_step, get_target_version};
+use crate::backup::model::BackupKind;
+use crate::utils::write_file_atomic;
+use crate::xml::{error::ParseError, extract::extract_element_tree};
+use anyhow::{anyhow, Result};
+use std::fs;
+
+// 存档中的版本属性: person 为 version, profile 为 game_version
+// 缺少该属性时按当前版本处理
+pub fn get_content_version(kind: BackupKind, content: &str) -> Result<u16, ParseError> {
+    let root = extract_element_tree(content)?;
+
+    let key = match kind {
+        BackupKind::Person => "version",
+        BackupKind::Profile => "game_version",
+    };
+
+    root.attr_list
+        .iter()
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.parse())
+        .unwrap_or(Ok(get_target_version(kind)))
+}
+
+// 依次执行升级步骤直到当前版本, 返回升级后的内容与执行的步骤
+// 已是当前版本时步骤为空
+pub fn migrate_content(
+    kind: BackupKind,
+    content: &str,
+) -> Result<(String, Vec<&'static MigrationStep>)> {
+    let target_version = get_target_version(kind);
+    let mut version = get_content_version(kind, content)?;
+
+    let mut content = content.to_string();
+    let mut step_list = vec![];
+
+    while version != target_version {
+        let step = find_step(kind, version).ok_or(ParseError::UnsupportedVersion {
+            expected: target_version.into(),
+            got: version.into(),
+        })?;
+
+        content = (step.migrate)(&content)?;
+
+        let migrated_version = get_content_version(kind, &content)?;
+
+        if migrated_version != step.to_version {
+            return Err(anyhow!(
+                "migrate {:?} {} -> {} error, got version: {}",
+                kind,
+                step.from_version,
+                step.to_version,
+                migrated_version
+            ));
+        }
+
+        version = migrated_version;
+        step_list.push(step);
+    }
+
+    Ok((content, step_list))
+}
+
+// 升级单个存档文件, 返回执行的步骤, dry_run 时不写入文件
+pub fn migrate_file(
+    folder_path: &str,
+    id: u64,
+    kind: BackupKind,
+    dry_run: bool,
+) -> Result<Vec<&'static MigrationStep>> {
+    let path = format!("{}/{}.{}", folder_path, id, kind.extension());
+
+    let content = fs::read_to_string(&path).map_err(|err| ParseError::from_io(&path, err))?;
+
+    let (new_content, step_list) = migrate_content(kind, &content)?;
+
+    if !dry_run && !step_list.is_empty() {
+        write_file_atomic(&path, new_content.as_bytes())?;
+    }
+
+    Ok(step_list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::migrate_file;
+    use crate::backup::model::BackupKind;
+    use crate::xml::extract::read_fixture_list;
+    use std::fs;
+
+    #[test]
+    fn migrate_file_dry_run() {
+        let (_, content) = read_fixture_list("person/v153").remove(0);
+
+        let folder = std::env::temp_dir().join(format!("rwr_migrate_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("1.person");
+        fs::write(&path, &content).unwrap();
+        let folder_path = folder.to_str().unwrap();
+
+        // dry-run 只返回将执行的步骤, 不修改文件
+        let step_list = migrate_file(folder_path, 1, BackupKind::Person, true).unwrap();
+        assert_eq!(step_list.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+
+        let step_list = migrate_file(folder_path, 1, BackupKind::Person, false).unwrap();
+        assert_eq!(step_list.len(), 1);
+        let migrated = fs::read_to_string(&path).unwrap();
+
+        let step_list = migrate_file(folder_path, 1, BackupKind::Person, false).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert!(migrated.contains(r#"version="154""#));
+        assert!(step_list.is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use crate::model::ResponseJson;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use std::io;
+use thiserror::Error;
+
+// 存档解析错误, 每种错误对应 ResponseJson 中不同的 code
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("file not found: {path}")]
+    NotFound { path: String },
+    #[error("read file {path} error: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("malformed xml at position {position}: {message}")]
+    MalformedXml { position: usize, message: String },
+    #[error("invalid attribute value at position {position}: {key}=\"{value}\"")]
+    InvalidAttr {
+        position: usize,
+        key: String,
+        value: String,
+    },
+    #[error("unsupported version: expected {expected}, got {got}")]
+    UnsupportedVersion { expected: u64, got: u64 },
+}
+
+impl ParseError {
+    pub fn from_io(path: &str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound {
+                path: path.to_string(),
+            },
+            _ => Self::Io {
+                path: path.to_string(),
+                source: err,
+            },
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::NotFound { .. } => 1001,
+            Self::Io { .. } => 1002,
+            Self::MalformedXml { .. } => 1003,
+            Self::InvalidAttr { .. } => 1004,
+            Self::UnsupportedVersion { .. } => 1005,
+        }
+    }
+}
+
+impl ResponseError for ParseError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound { .. } => StatusCode::NOT_FOUND,
+            Self::Io { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ResponseJson {
+            status: self.status_code().as_u16() as i32,
+            code: self.code(),
+            message: self.to_string(),
+        })
+    }
+}
+
+// 批量操作返回 anyhow::Error, 其中的解析错误仍按错误码返回
+pub fn parse_error_response(err: &anyhow::Error) -> Option<HttpResponse> {
+    err.downcast_ref::<ParseError>()
+        .map(ResponseError::error_response)
+}
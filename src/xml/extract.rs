@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::error::ParseError;
+use super::model::{XmlAttr, XmlElement, XmlNode};
+use quick_xml::{events::BytesStart, events::Event, Reader};
+use std::io::BufRead;
+use std::str;
+
+fn malformed<E: std::fmt::Display>(position: usize, err: E) -> ParseError {
+    ParseError::MalformedXml {
+        position,
+        message: err.to_string(),
+    }
+}
+
+fn extract_element<B: BufRead>(
+    e: &BytesStart,
+    reader: &Reader<B>,
+    position: usize,
+) -> Result<XmlElement, ParseError> {
+    let mut element = XmlElement {
+        name: str::from_utf8(e.name())
+            .map_err(|err| malformed(position, err))?
+            .to_string(),
+        ..XmlElement::default()
+    };
+
+    for attr in e.attributes() {
+        let attr_unwrap_res = attr.map_err(|err| malformed(position, err))?;
+
+        element.attr_list.push(XmlAttr {
+            key: str::from_utf8(attr_unwrap_res.key)
+                .map_err(|err| malformed(position, err))?
+                .to_string(),
+            value: attr_unwrap_res
+                .unescape_and_decode_value(reader)
+                .map_err(|err| malformed(position, err))?,
+            position,
+        });
+    }
+
+    Ok(element)
+}
+
+// 挂到当前父元素下, 没有父元素时即为根元素
+fn push_node(
+    stack: &mut [XmlElement],
+    root: &mut Option<XmlElement>,
+    node: XmlNode,
+    position: usize,
+) -> Result<(), ParseError> {
+    match (stack.last_mut(), node) {
+        (Some(parent), node) => parent.child_list.push(node),
+        (None, XmlNode::Element(element)) => {
+            if root.is_some() {
+                let message = format!("multiple root element: {}", element.name);
+                return Err(malformed(position, message));
+            }
+            *root = Some(element);
+        }
+        // 文档级别的注释与空白忽略
+        (None, _) => (),
+    }
+
+    Ok(())
+}
+
+// 将整个文档读取为元素树, 忽略 xml 声明与处理指令
+pub fn extract_element_tree(content: &str) -> Result<XmlElement, ParseError> {
+    let mut reader = Reader::from_str(content);
+
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut stack: Vec<XmlElement> = vec![];
+    let mut root: Option<XmlElement> = None;
+
+    loop {
+        let position = reader.buffer_position();
+
+        let event = reader
+            .read_event(&mut buf)
+            .map_err(|err| malformed(reader.buffer_position(), err))?;
+
+        match event {
+            Event::Start(e) => {
+                stack.push(extract_element(&e, &reader, position)?);
+            }
+            Event::Empty(e) => {
+                let element = extract_element(&e, &reader, position)?;
+                push_node(&mut stack, &mut root, XmlNode::Element(element), position)?;
+            }
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| malformed(position, "unexpected end tag"))?;
+                push_node(&mut stack, &mut root, XmlNode::Element(element), position)?;
+            }
+            Event::Text(e) => {
+                let text = e
+                    .unescape_and_decode(&reader)
+                    .map_err(|err| malformed(position, err))?;
+                push_node(&mut stack, &mut root, XmlNode::Text(text), position)?;
+            }
+            // quick-xml 读取 CDATA 时会将内容转义, 需还原
+            Event::CData(e) => {
+                let text = e
+                    .unescape_and_decode(&reader)
+                    .map_err(|err| malformed(position, err))?;
+                push_node(&mut stack, &mut root, XmlNode::CData(text), position)?;
+            }
+            Event::Comment(e) => {
+                let text = str::from_utf8(&e)
+                    .map_err(|err| malformed(position, err))?
+                    .to_string();
+                push_node(&mut stack, &mut root, XmlNode::Comment(text), position)?;
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+
+        buf.clear();
+    }
+
+    let position = reader.buffer_position();
+
+    if let Some(element) = stack.last() {
+        let message = format!("unclosed element: {}", element.name);
+        return Err(malformed(position, message));
+    }
+
+    root.ok_or_else(|| malformed(position, "root element not found"))
+}
+
+// 读取 tests/fixtures/{kind} 下的全部存档, 返回 (文件名, 内容)
+// kind 可带子目录, 如 person/v153, 扩展名取第一段
+#[cfg(test)]
+pub fn read_fixture_list(kind: &str) -> Vec<(String, String)> {
+    let extension = kind.split('/').next().unwrap_or(kind);
+    let folder_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(kind);
+
+    let mut fixture_list: Vec<(String, String)> = std::fs::read_dir(folder_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            (name, std::fs::read_to_string(path).unwrap())
+        })
+        .collect();
+
+    fixture_list.sort();
+
+    fixture_list
+}
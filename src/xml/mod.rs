@@ -0,0 +1,5 @@
+// SPDX-License-Identifier: GPL-3.0-only
+pub mod error;
+pub mod extract;
+pub mod model;
+pub mod save;
@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::error::ParseError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct XmlAttr {
+    pub key: String,
+    pub value: String,
+    // 所在元素在文件中的位置, 仅用于错误提示
+    #[serde(skip)]
+    pub position: usize,
+}
+
+impl XmlAttr {
+    pub fn parse<T: FromStr>(&self) -> Result<T, ParseError> {
+        self.value.parse().map_err(|_| ParseError::InvalidAttr {
+            position: self.position,
+            key: self.key.clone(),
+            value: self.value.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+    CData(String),
+    Comment(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attr_list: Vec<XmlAttr>,
+    pub child_list: Vec<XmlNode>,
+}
+
+// 未识别的子节点, index 为其在父元素所有子节点中的原始位置
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExtraXmlNode {
+    pub index: usize,
+    pub node: XmlNode,
+}
+
+// 模型未识别的属性与子节点(游戏新版本或 mod 新增), 保存时原样写回
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct XmlExtra {
+    #[serde(default)]
+    pub attr_list: Vec<XmlAttr>,
+    #[serde(default)]
+    pub child_list: Vec<ExtraXmlNode>,
+}
+
+impl XmlExtra {
+    pub fn is_empty(&self) -> bool {
+        self.attr_list.is_empty() && self.child_list.is_empty()
+    }
+
+    pub fn push_child(&mut self, index: usize, node: XmlNode) {
+        self.child_list.push(ExtraXmlNode { index, node });
+    }
+}
+
+#[cfg(test)]
+impl XmlElement {
+    // 属性顺序与数字写法(如 10.0 与 10)不影响语义, 比较前统一
+    pub fn normalize(&mut self) {
+        for attr in self.attr_list.iter_mut() {
+            attr.position = 0;
+
+            if let Ok(value) = attr.value.parse::<f64>() {
+                attr.value = value.to_string();
+            }
+        }
+
+        self.attr_list.sort_by(|a, b| a.key.cmp(&b.key));
+
+        for child in self.child_list.iter_mut() {
+            if let XmlNode::Element(child) = child {
+                child.normalize();
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0-only
+use super::model::{ExtraXmlNode, XmlElement, XmlExtra, XmlNode};
+use anyhow::Result;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use std::io::Write;
+
+pub fn push_extra_attr_list(tag: &mut BytesStart, extra: &XmlExtra) {
+    for attr in extra.attr_list.iter() {
+        tag.push_attribute((attr.key.as_str(), attr.value.as_str()));
+    }
+}
+
+pub fn write_node<W: Write>(writer: &mut Writer<W>, node: &XmlNode) -> Result<()> {
+    match node {
+        XmlNode::Element(element) => write_element(writer, element)?,
+        XmlNode::Text(text) => {
+            writer.write_event(Event::Text(BytesText::from_plain_str(text)))?;
+        }
+        XmlNode::CData(text) => {
+            writer.write_event(Event::CData(BytesText::from_escaped_str(text.as_str())))?;
+        }
+        XmlNode::Comment(text) => {
+            writer.write_event(Event::Comment(BytesText::from_escaped_str(text.as_str())))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_element<W: Write>(writer: &mut Writer<W>, element: &XmlElement) -> Result<()> {
+    let mut tag = BytesStart::owned_name(element.name.as_bytes());
+
+    for attr in element.attr_list.iter() {
+        tag.push_attribute((attr.key.as_str(), attr.value.as_str()));
+    }
+
+    if element.child_list.is_empty() {
+        writer.write_event(Event::Empty(tag))?;
+        return Ok(());
+    }
+
+    writer.write_event(Event::Start(tag))?;
+
+    for child in element.child_list.iter() {
+        write_node(writer, child)?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::borrowed(element.name.as_bytes())))?;
+
+    Ok(())
+}
+
+// 写入已识别的叶子元素(order / item / item_group), 同时写回未识别的属性与子节点
+pub fn write_leaf_element<W: Write>(
+    writer: &mut Writer<W>,
+    mut tag: BytesStart,
+    extra: &XmlExtra,
+) -> Result<()> {
+    push_extra_attr_list(&mut tag, extra);
+
+    if extra.child_list.is_empty() {
+        writer.write_event(Event::Empty(tag))?;
+        return Ok(());
+    }
+
+    let name = tag.name().to_vec();
+
+    writer.write_event(Event::Start(tag))?;
+    ExtraChildWriter::new(extra).write_rest(writer)?;
+    writer.write_event(Event::End(BytesEnd::owned(name)))?;
+
+    Ok(())
+}
+
+// 按原始位置将未识别的子节点穿插写回已识别的子元素之间
+pub struct ExtraChildWriter<'a> {
+    child_list: &'a [ExtraXmlNode],
+    pos: usize,
+    written: usize,
+}
+
+impl<'a> ExtraChildWriter<'a> {
+    pub fn new(extra: &'a XmlExtra) -> Self {
+        Self {
+            child_list: &extra.child_list,
+            pos: 0,
+            written: 0,
+        }
+    }
+
+    // 每写入一个已识别的子元素前调用
+    pub fn write_before_known<W: Write>(&mut self, writer: &mut Writer<W>) -> Result<()> {
+        while let Some(child) = self.child_list.get(self.pos) {
+            if child.index > self.written {
+                break;
+            }
+
+            write_node(writer, &child.node)?;
+            self.pos += 1;
+            self.written += 1;
+        }
+
+        self.written += 1;
+
+        Ok(())
+    }
+
+    pub fn write_rest<W: Write>(&mut self, writer: &mut Writer<W>) -> Result<()> {
+        for child in self.child_list[self.pos..].iter() {
+            write_node(writer, &child.node)?;
+        }
+
+        self.pos = self.child_list.len();
+
+        Ok(())
+    }
+}
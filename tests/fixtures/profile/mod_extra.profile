@@ -0,0 +1,13 @@
+<?xml version="1.0" encoding="utf-8"?>
+<profile game_version="154" username="M &amp; M" digest="" sid="ID1" rid="r1" squad_tag="&lt;A&gt;" color="1 0 0 1" banned="0">
+<!-- server notes -->
+<achievements>
+<achievement key="marathon" unlocked="1"/>
+</achievements>
+<stats kills="5" deaths="-1" time_played="3600" player_kills="0" teamkills="0" longest_kill_streak="2" targets_destroyed="0" vehicles_destroyed="0" soldiers_healed="0" times_got_healed="0" distance_moved="0" shots_fired="50" throwables_thrown="0" rank_progression="0" headshots="3">
+<monitor name="kill combo" longest="2" current="1"/>
+<weapon_stats key="g36.weapon" kills="5"/>
+<monitor name="death streak" longest="1"/>
+</stats>
+<history season="3"/>
+</profile>
@@ -0,0 +1,3 @@
+<profile game_version="154" username="NEW" digest="" sid="ID2" rid="r2" squad_tag="" color="0 0 0 1">
+<stats kills="0" deaths="0" time_played="0" player_kills="0" teamkills="0" longest_kill_streak="0" targets_destroyed="0" vehicles_destroyed="0" soldiers_healed="0" times_got_healed="0" distance_moved="0" shots_fired="0" throwables_thrown="0" rank_progression="0"/>
+</profile>
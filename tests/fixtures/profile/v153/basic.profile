@@ -0,0 +1,6 @@
+<?xml version="1.0" encoding="utf-8"?>
+<profile game_version="153" username="ALICE" digest="" sid="765" rid="abc" squad_tag="" color="0.5 0.5 0.5 1">
+<stats kills="10" deaths="2" time_played="100.5" player_kills="0" teamkills="0" longest_kill_streak="3" targets_destroyed="0" vehicles_destroyed="0" soldiers_healed="0" times_got_healed="0" distance_moved="10.0" shots_fired="100" throwables_thrown="1" rank_progression="0.5">
+<monitor name="kill combo" longest="3"/>
+</stats>
+</profile>
@@ -0,0 +1,7 @@
+<?xml version="1.0" encoding="utf-8"?>
+<profile game_version="154" username="BRAVO 2" digest="" sid="ID76561198000000000" rid="6f1c2b7d0e8a4f3c9b5d2e1a0f7c6b8d" squad_tag="[RWR]" color="0.595 0.476 0.544 1">
+<stats kills="18342" deaths="2213" time_played="1.21e6" player_kills="12" teamkills="31" longest_kill_streak="87" targets_destroyed="412" vehicles_destroyed="233" soldiers_healed="519" times_got_healed="604" distance_moved="3412850.5" shots_fired="401255" throwables_thrown="2210" rank_progression="0.8125">
+<monitor name="kill combo" longest="87"/>
+<monitor name="death streak" longest="6"/>
+</stats>
+</profile>